@@ -1,19 +1,32 @@
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{Request, RequestInit, RequestMode, Response};
-
-// Constante para el endpoint de chat HTTP
-const CHAT_ENDPOINT: &str = "https://newsapi.org/v2/everything";
+use std::rc::Rc;
+use crate::core::websocket::websocket_chat_client::WebSocketChatClient;
+use crate::core::websocket::websocket_manager::{AuthCredentials, WebSocketMessage};
+use crate::core::sse::sse_chat_client::SseChatClient;
+use crate::core::long_polling::long_polling_client::LongPollingClient;
+use crate::core::transport::chat_transport::ChatTransport;
+use crate::core::transport::http_request::HttpHandler;
+use crate::core::transport::http_transport::{HttpTransport, CHAT_ENDPOINT};
+use crate::core::transport::hybrid_transport::HybridTransport;
 
 // URL interna del WebSocket - será inyectada en build time
 const WEBSOCKET_ENDPOINT: &str = env!("WEBSOCKET_URL");
 
+// Tiempo durante el que el usuario puede editar o retirar un mensaje enviado
+pub const DEFAULT_EDIT_WINDOW_SECS: u32 = 300;
+
+// Nombre del bot en la cabecera mientras no atiende un agente
+pub const DEFAULT_BOT_NAME: &str = "Grace";
+
 #[derive(Debug, Clone)]
 pub enum ChatMode {
     Http,      // Usar HTTP/REST API (actual)
     WebSocket, // Usar WebSocket en tiempo real
     Hybrid,    // Combinar ambos
+    Sse,       // Server-Sent Events para recibir, HTTP POST para enviar
+    LongPolling, // Peticiones HTTP repetidas para recibir, HTTP POST para enviar
 }
 
 #[derive(Debug, Clone)]
@@ -21,11 +34,19 @@ pub struct GraceChatConfig {
     pub api_key: String,
     pub welcome_message: String,
     pub theme: String,
+    pub bot_name: String,
     // Nuevas opciones para WebSocket
     pub mode: ChatMode,
     pub websocket_url: Option<String>,
     pub user_id: Option<String>,
-    // No incluimos el manager en la estructura para evitar problemas con Clone
+    // Endpoint HTTP que responde en streaming (NDJSON o SSE)
+    pub stream_url: Option<String>,
+    // Conexión compartida entre los clones de la configuración
+    pub websocket_client: Option<WebSocketChatClient>,
+    pub sse_client: Option<SseChatClient>,
+    pub long_polling_client: Option<LongPollingClient>,
+    // Segundos tras el envío en los que se puede editar o retirar un mensaje (0 lo desactiva)
+    pub edit_window_secs: u32,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -35,106 +56,21 @@ pub struct ChatMessage {
     pub timestamp: String,
 }
 
-#[derive(Deserialize)]
-struct NewsApiResponse {
-    status: String,
-    #[serde(rename = "totalResults")]
-    total_results: Option<i32>,
-    articles: Option<Vec<Article>>,
-    message: Option<String>,
-}
-
-#[derive(Deserialize)]
-struct Article {
-    title: String,
-    description: Option<String>,
-    url: String,
-}
-
-// Estructura para manejar respuestas HTTP de forma centralizada
-struct HttpHandler;
-
-impl HttpHandler {
-    // Hacer una petición HTTP GET
-    async fn get_request(url: &str) -> Result<Response, JsValue> {
-        let opts = RequestInit::new();
-        opts.set_method("GET");
-        opts.set_mode(RequestMode::Cors);
-
-        let request = Request::new_with_str_and_init(url, &opts)
-            .map_err(|e| JsValue::from_str(&format!("Failed to create request: {:?}", e)))?;
-
-        let window = web_sys::window().unwrap();
-        let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
-        let resp: Response = resp_value.dyn_into().unwrap();
-        
-        Ok(resp)
-    }
-
-    // Manejar respuestas de validación de API Key
-    fn handle_validation_response(status: u16, error_text: Option<String>) -> (bool, String) {
-        match status {
-            200 => (true, "API Key validation: SUCCESS".to_string()),
-            400 => (false, "API Key validation: FAILED - Bad Request".to_string()),
-            401 => {
-                let message = if let Some(text) = error_text {
-                    if text.contains("apiKeyInvalid") {
-                        "API Key validation: FAILED - Invalid API Key"
-                    } else if text.contains("apiKeyMissing") {
-                        "API Key validation: FAILED - Missing API Key"
-                    } else if text.contains("apiKeyDisabled") {
-                        "API Key validation: FAILED - API Key Disabled"
-                    } else if text.contains("apiKeyExhausted") {
-                        "API Key validation: FAILED - API Key Exhausted"
-                    } else {
-                        "API Key validation: FAILED - Unauthorized"
-                    }
-                } else {
-                    "API Key validation: FAILED - Unauthorized"
-                };
-                (false, message.to_string())
-            },
-            429 => (false, "API Key validation: FAILED - Rate Limited".to_string()),
-            500 => (false, "API Key validation: FAILED - Server Error".to_string()),
-            _ => (false, format!("API Key validation: FAILED - Unexpected status: {}", status)),
-        }
-    }
-
-    // Manejar respuestas de chat (para el usuario) - Unificado con validación
-    fn handle_chat_response(status: u16, error_text: Option<String>) -> String {
-        match status {
-            200 => "Success".to_string(), // Este caso se maneja diferente en chat
-            400 => "Lo siento, tu pregunta no es válida. ¿Podrías reformularla?".to_string(),
-            401 => {
-                // Podríamos hacer análisis similar al de validación si fuera necesario
-                if let Some(text) = error_text {
-                    if text.contains("apiKeyInvalid") || text.contains("apiKeyMissing") {
-                        "Tu API key no es válida. Por favor, contacta al administrador.".to_string()
-                    } else if text.contains("apiKeyDisabled") || text.contains("apiKeyExhausted") {
-                        "Tu API key ha sido deshabilitada o agotada. Contacta al administrador.".to_string()
-                    } else {
-                        "Hay un problema con la configuración del chat. Por favor, contacta al administrador.".to_string()
-                    }
-                } else {
-                    "Hay un problema con la configuración del chat. Por favor, contacta al administrador.".to_string()
-                }
-            },
-            429 => "Demasiadas consultas en este momento. Por favor, espera un momento e intenta de nuevo.".to_string(),
-            500 => "El servicio no está disponible en este momento. Por favor, intenta más tarde.".to_string(),
-            _ => format!("Error inesperado (código {}). Por favor, intenta de nuevo.", status),
-        }
-    }
-}
-
 impl GraceChatConfig {
     pub fn new(api_key: String, welcome_message: String, theme: String) -> Self {
         Self {
             api_key,
             welcome_message,
             theme,
+            bot_name: DEFAULT_BOT_NAME.to_string(),
             mode: ChatMode::Http, // Default a HTTP
             websocket_url: None,
             user_id: None,
+            stream_url: None,
+            websocket_client: None,
+            sse_client: None,
+            long_polling_client: None,
+            edit_window_secs: DEFAULT_EDIT_WINDOW_SECS,
         }
     }
 
@@ -145,13 +81,20 @@ impl GraceChatConfig {
         theme: String,
         user_id: String
     ) -> Self {
+        let websocket_client = WebSocketChatClient::new(WEBSOCKET_ENDPOINT.to_string(), user_id.clone(), Self::api_key_credentials(&api_key));
         Self {
             api_key,
             welcome_message,
             theme,
+            bot_name: DEFAULT_BOT_NAME.to_string(),
             mode: ChatMode::WebSocket,
+            websocket_client: Some(websocket_client),
             websocket_url: Some(WEBSOCKET_ENDPOINT.to_string()),
             user_id: Some(user_id),
+            stream_url: None,
+            sse_client: None,
+            long_polling_client: None,
+            edit_window_secs: DEFAULT_EDIT_WINDOW_SECS,
         }
     }
 
@@ -163,16 +106,55 @@ impl GraceChatConfig {
         websocket_url: String,
         user_id: String
     ) -> Self {
+        let websocket_client = WebSocketChatClient::new(websocket_url.clone(), user_id.clone(), Self::api_key_credentials(&api_key));
         Self {
             api_key,
             welcome_message,
             theme,
+            bot_name: DEFAULT_BOT_NAME.to_string(),
             mode: ChatMode::WebSocket,
+            websocket_client: Some(websocket_client),
             websocket_url: Some(websocket_url),
             user_id: Some(user_id),
+            stream_url: None,
+            sse_client: None,
+            long_polling_client: None,
+            edit_window_secs: DEFAULT_EDIT_WINDOW_SECS,
         }
     }
 
+    // Constructor para modo SSE: eventos por `sse_url` y mensajes por POST a la misma URL
+    pub fn new_sse(
+        api_key: String,
+        welcome_message: String,
+        theme: String,
+        sse_url: String,
+        user_id: String
+    ) -> Self {
+        let sse_client = SseChatClient::new(sse_url, api_key.clone(), user_id.clone());
+        let mut config = Self::new(api_key, welcome_message, theme);
+        config.mode = ChatMode::Sse;
+        config.user_id = Some(user_id);
+        config.sse_client = Some(sse_client);
+        config
+    }
+
+    // Constructor para modo long-polling: sondeos a `polling_url` y mensajes por POST a la misma URL
+    pub fn new_long_polling(
+        api_key: String,
+        welcome_message: String,
+        theme: String,
+        polling_url: String,
+        user_id: String
+    ) -> Self {
+        let long_polling_client = LongPollingClient::new(polling_url, api_key.clone(), user_id.clone());
+        let mut config = Self::new(api_key, welcome_message, theme);
+        config.mode = ChatMode::LongPolling;
+        config.user_id = Some(user_id);
+        config.long_polling_client = Some(long_polling_client);
+        config
+    }
+
     // Configurar modo de chat
     pub fn set_mode(&mut self, mode: ChatMode) {
         self.mode = mode;
@@ -180,16 +162,67 @@ impl GraceChatConfig {
 
     // Configurar WebSocket con URL interna
     pub fn set_websocket_config_with_internal_url(&mut self, user_id: String) {
-        self.websocket_url = Some(WEBSOCKET_ENDPOINT.to_string());
-        self.user_id = Some(user_id);
+        self.set_websocket_config(WEBSOCKET_ENDPOINT.to_string(), user_id);
     }
 
     // Configurar WebSocket (mantener compatibilidad)
     pub fn set_websocket_config(&mut self, url: String, user_id: String) {
+        self.websocket_client = Some(WebSocketChatClient::new(url.clone(), user_id.clone(), Self::api_key_credentials(&self.api_key)));
         self.websocket_url = Some(url);
         self.user_id = Some(user_id);
     }
 
+    // Configurar SSE como alternativa al WebSocket en modo híbrido
+    pub fn set_sse_config(&mut self, url: String, user_id: String) {
+        self.sse_client = Some(SseChatClient::new(url, self.api_key.clone(), user_id.clone()));
+        self.user_id = Some(user_id);
+    }
+
+    // Configurar long-polling como último recurso en modo híbrido
+    pub fn set_long_polling_config(&mut self, url: String, user_id: String) {
+        self.long_polling_client = Some(LongPollingClient::new(url, self.api_key.clone(), user_id.clone()));
+        self.user_id = Some(user_id);
+    }
+
+    // Autenticar el WebSocket con un token de corta duración en lugar del API Key.
+    // Se aplica desde la próxima conexión.
+    pub fn set_auth_token(&mut self, token: String) {
+        if let Some(client) = &self.websocket_client {
+            client.manager().set_credentials(Some(AuthCredentials::Token(token)));
+        }
+    }
+
+    // Frames binarios en MessagePack, negociados con el subprotocolo WebSocket.
+    // Solo para servidores que negocian; sin esta opción se habla JSON.
+    pub fn set_message_pack(&mut self, enabled: bool) {
+        if let Some(client) = &self.websocket_client {
+            client.manager().set_message_pack(enabled);
+        }
+    }
+
+    // Mantener la conversación en un room propio, sea cual sea el transporte
+    pub fn set_room(&mut self, room: String) -> Result<(), JsValue> {
+        if let Some(client) = &self.websocket_client {
+            client.set_room(Some(room.clone()))?;
+        }
+        if let Some(client) = &self.sse_client {
+            client.set_room(Some(room.clone()))?;
+        }
+        if let Some(client) = &self.long_polling_client {
+            client.set_room(Some(room))?;
+        }
+        Ok(())
+    }
+
+    // Sin API Key la conexión se abre sin paso de autenticación
+    fn api_key_credentials(api_key: &str) -> Option<AuthCredentials> {
+        if api_key.is_empty() {
+            None
+        } else {
+            Some(AuthCredentials::ApiKey(api_key.to_string()))
+        }
+    }
+
     // Verificar si puede usar WebSocket
     pub fn can_use_websocket(&self) -> bool {
         self.websocket_url.is_some() && self.user_id.is_some()
@@ -236,120 +269,76 @@ impl GraceChatConfig {
         Ok(is_valid)
     }
 
+    // Generar el id de un mensaje nuevo del usuario
+    pub fn new_message_id(&self) -> String {
+        WebSocketMessage::new_id(self.user_id.as_deref().unwrap_or("user"))
+    }
+
     // Procesar mensaje del usuario - soporta HTTP y WebSocket
     pub async fn process_chat_message(&self, user_message: &str) -> Result<String, JsValue> {
-        match self.mode {
-            ChatMode::Http => {
-                self.process_http_message(user_message).await
-            }
-            ChatMode::WebSocket => {
-                self.process_websocket_message(user_message).await
-            }
-            ChatMode::Hybrid => {
-                // En modo híbrido, preferir WebSocket si está configurado
-                if self.can_use_websocket() {
-                    self.process_websocket_message(user_message).await
-                } else {
-                    self.process_http_message(user_message).await
-                }
-            }
-        }
+        self.process_chat_message_with_id(&self.new_message_id(), user_message).await
     }
 
-    // Procesar mensaje via HTTP (método original)
-    async fn process_http_message(&self, user_message: &str) -> Result<String, JsValue> {
-        // Validar que tenemos API key
-        if self.api_key.is_empty() {
-            return Err(JsValue::from_str("API key is required"));
-        }
+    // Procesar mensaje del usuario con un id ya asignado, para seguir su entrega
+    pub async fn process_chat_message_with_id(&self, message_id: &str, user_message: &str) -> Result<String, JsValue> {
+        self.process_chat_message_streaming(message_id, user_message, |_| {}).await
+    }
 
-        // Crear URL con el mensaje del usuario como query
-        let encoded_message = js_sys::encode_uri_component(user_message);
-        let url = format!(
-            "{}?q={}&apiKey={}", 
-            CHAT_ENDPOINT, 
-            encoded_message.as_string().unwrap_or_default(),
-            self.api_key
-        );
-        
-        web_sys::console::log_1(&format!("Chat request URL: {}", url).into());
+    // Igual que `process_chat_message_with_id`, pasando a `on_delta` cada parte
+    // de una respuesta HTTP en streaming. Crea un transporte nuevo en cada
+    // llamada; el widget crea uno con `build_transport` y lo reutiliza.
+    pub async fn process_chat_message_streaming<F>(&self, message_id: &str, user_message: &str, mut on_delta: F) -> Result<String, JsValue>
+    where
+        F: FnMut(&str),
+    {
+        let reply = self.build_transport().send(message_id, user_message, &mut on_delta).await?;
+        Ok(reply.text)
+    }
 
-        let resp = HttpHandler::get_request(&url).await?;
-        let status = resp.status();
-        
-        match status {
-            200 => {
-                // Leer el cuerpo de la respuesta
-                let text_promise = resp.text().map_err(|_| JsValue::from_str("Failed to read response"))?;
-                let text = JsFuture::from(text_promise).await?;
-                let json_text = text.as_string().unwrap_or_default();
-                
-                // Parsear la respuesta JSON
-                match serde_json::from_str::<NewsApiResponse>(&json_text) {
-                    Ok(news_response) => {
-                        if news_response.status == "ok" {
-                            self.format_news_response(&news_response)
-                        } else {
-                            Ok(format!("Lo siento, hubo un problema: {}", 
-                                news_response.message.unwrap_or("Error desconocido".to_string())))
-                        }
-                    },
-                    Err(_) => {
-                        web_sys::console::log_1(&format!("Failed to parse JSON response: {}", json_text).into());
-                        Ok("Lo siento, no pude procesar la respuesta del servidor.".to_string())
-                    }
-                }
-            },
-            _ => {
-                // Usar el handler centralizado para manejar errores de chat
-                Ok(HttpHandler::handle_chat_response(status, None))
-            }
-        }
+    // Configurar el endpoint HTTP con respuestas en streaming
+    pub fn set_stream_url(&mut self, url: String) {
+        self.stream_url = Some(url);
     }
 
-    // Procesar mensaje via WebSocket
-    async fn process_websocket_message(&self, user_message: &str) -> Result<String, JsValue> {
-        // En una implementación real, crearíamos el WebSocket aquí
-        // Por ahora, simulamos la funcionalidad
-        if self.can_use_websocket() {
-            web_sys::console::log_1(&format!("Would send via WebSocket to: {:?}", self.websocket_url).into());
-            web_sys::console::log_1(&format!("Message: {}", user_message).into());
-            
-            // Simular respuesta
-            Ok(format!("Echo via WebSocket: {}", user_message))
-        } else {
-            Err(JsValue::from_str("WebSocket not configured"))
-        }
+    pub fn set_bot_name(&mut self, name: String) {
+        self.bot_name = name;
     }
 
-    // Formatear la respuesta de noticias en un mensaje amigable
-    fn format_news_response(&self, news_response: &NewsApiResponse) -> Result<String, JsValue> {
-        let total_results = news_response.total_results.unwrap_or(0);
-        
-        if total_results == 0 {
-            return Ok("No encontré noticias relacionadas con tu consulta. ¿Podrías probar con otros términos?".to_string());
-        }
+    pub fn set_edit_window(&mut self, secs: u32) {
+        self.edit_window_secs = secs;
+    }
 
-        let empty_vec = vec![];
-        let articles = news_response.articles.as_ref().unwrap_or(&empty_vec);
-        let limited_articles = articles.iter().take(3); // Mostrar solo las primeras 3 noticias
-        
-        let mut response = format!("Encontré {} noticias relacionadas. Aquí están las más relevantes:\n\n", total_results);
-        
-        for (index, article) in limited_articles.enumerate() {
-            response.push_str(&format!(
-                "{}. **{}**\n{}\n[Leer más]({})\n\n",
-                index + 1,
-                article.title,
-                article.description.as_ref().unwrap_or(&"Sin descripción disponible.".to_string()),
-                article.url
-            ));
-        }
-        
-        if total_results > 3 {
-            response.push_str(&format!("Y {} noticias más...", total_results - 3));
-        }
-        
-        Ok(response)
+    // Editar y retirar mensajes viaja como frames, así que necesita el WebSocket
+    pub fn can_edit_messages(&self) -> bool {
+        self.edit_window_secs > 0 && matches!(self.mode, ChatMode::WebSocket | ChatMode::Hybrid)
+    }
+
+    // Transporte según el modo. Los clientes de tiempo real se comparten entre
+    // clones de la configuración; en modo híbrido se prueban en orden
+    // (WebSocket, SSE, long-polling) con HTTP como último recurso.
+    pub fn build_transport(&self) -> Rc<dyn ChatTransport> {
+        let http: Rc<dyn ChatTransport> = Rc::new(HttpTransport::new(self.api_key.clone(), self.stream_url.clone()));
+
+        let websocket = self.websocket_client.clone()
+            .filter(|_| self.can_use_websocket())
+            .map(|client| Rc::new(client) as Rc<dyn ChatTransport>);
+        let sse = self.sse_client.clone().map(|client| Rc::new(client) as Rc<dyn ChatTransport>);
+        let long_polling = self.long_polling_client.clone().map(|client| Rc::new(client) as Rc<dyn ChatTransport>);
+
+        let transport = match self.mode {
+            ChatMode::Http => Some(http),
+            ChatMode::WebSocket => websocket,
+            ChatMode::Sse => sse,
+            ChatMode::LongPolling => long_polling,
+            ChatMode::Hybrid => {
+                let transports: Vec<_> = [websocket, sse, long_polling, Some(http)].into_iter().flatten().collect();
+                Some(Rc::new(HybridTransport::new(transports)) as Rc<dyn ChatTransport>)
+            }
+        };
+
+        transport.unwrap_or_else(|| {
+            web_sys::console::log_1(&format!("{:?} mode is not configured, using HTTP", self.mode).into());
+            Rc::new(HttpTransport::new(self.api_key.clone(), self.stream_url.clone()))
+        })
     }
 }
\ No newline at end of file
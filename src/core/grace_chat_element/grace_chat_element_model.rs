@@ -1,12 +1,20 @@
 use wasm_bindgen::prelude::*;
 use web_sys::{*, HtmlInputElement, KeyboardEvent};
 use crate::core::grace_chat_config::grace_chat_config_model::{GraceChatConfig, ChatMode};
+use crate::core::websocket::websocket_manager::{cancel_timer, set_timer, DeliveryStatus, WebSocketMessage};
+use crate::core::transport::chat_transport::{ChatTransport, Receipt, TransportEvent};
+use super::typing_notifier::TypingNotifier;
+use std::rc::{Rc, Weak};
+
+// Un "está escribiendo" remoto sin frame de fin desaparece tras este tiempo
+const REMOTE_TYPING_EXPIRY_MS: u32 = 6_000;
 
 // Web Component principal
 #[wasm_bindgen]
 pub struct GraceChatElement {
     element: HtmlElement,
     config: Option<GraceChatConfig>,
+    transport: Option<Rc<dyn ChatTransport>>,
     initialized: bool,
 }
 
@@ -21,6 +29,7 @@ impl GraceChatElement {
         Ok(GraceChatElement {
             element,
             config: None,
+            transport: None,
             initialized: false,
         })
     }
@@ -40,13 +49,29 @@ impl GraceChatElement {
         // Solo necesitamos user-id para WebSocket (la URL es interna)
         let user_id = self.element.get_attribute("user-id");
         let mode = self.element.get_attribute("mode").unwrap_or("http".to_string());
+        // Token opcional para autenticar el WebSocket en lugar del API Key
+        let auth_token = self.element.get_attribute("auth-token");
+        // Room opcional para separar conversaciones (por ejemplo, una por página)
+        let room = self.element.get_attribute("room");
+        // Endpoint HTTP opcional que responde en streaming (NDJSON o SSE)
+        let stream_url = self.element.get_attribute("stream-url");
+        // Endpoint SSE (modo sse)
+        let sse_url = self.element.get_attribute("sse-url");
+        // Endpoint de long-polling (modo polling)
+        let polling_url = self.element.get_attribute("polling-url");
+        // Segundos para editar o retirar un mensaje enviado (0 lo desactiva)
+        let edit_window = self.element.get_attribute("edit-window");
+        // Nombre del bot en la cabecera
+        let bot_name = self.element.get_attribute("bot-name");
+        // Frames binarios en MessagePack (el servidor debe negociar el subprotocolo)
+        let message_pack = self.element.get_attribute("message-pack");
 
         if api_key.is_empty() {
             return Err(JsValue::from_str("API Key is required"));
         }
 
         // Crear configuración según el modo
-        let config = match mode.as_str() {
+        let mut config = match mode.as_str() {
             "websocket" => {
                 if let Some(uid) = user_id {
                     GraceChatConfig::new_websocket_with_internal_url(api_key, welcome, theme, uid)
@@ -54,23 +79,69 @@ impl GraceChatElement {
                     return Err(JsValue::from_str("user-id is required for WebSocket mode"));
                 }
             }
+            "sse" => {
+                match (sse_url, user_id) {
+                    (Some(url), Some(uid)) => GraceChatConfig::new_sse(api_key, welcome, theme, url, uid),
+                    (None, _) => return Err(JsValue::from_str("sse-url is required for SSE mode")),
+                    (_, None) => return Err(JsValue::from_str("user-id is required for SSE mode")),
+                }
+            }
+            "polling" => {
+                match (polling_url, user_id) {
+                    (Some(url), Some(uid)) => GraceChatConfig::new_long_polling(api_key, welcome, theme, url, uid),
+                    (None, _) => return Err(JsValue::from_str("polling-url is required for polling mode")),
+                    (_, None) => return Err(JsValue::from_str("user-id is required for polling mode")),
+                }
+            }
             "hybrid" => {
                 let mut config = GraceChatConfig::new(api_key, welcome, theme);
                 config.set_mode(ChatMode::Hybrid);
                 if let Some(uid) = user_id {
-                    config.set_websocket_config_with_internal_url(uid);
+                    config.set_websocket_config_with_internal_url(uid.clone());
+                    // Alternativas si el WebSocket no consigue conectar
+                    if let Some(url) = sse_url {
+                        config.set_sse_config(url, uid.clone());
+                    }
+                    if let Some(url) = polling_url {
+                        config.set_long_polling_config(url, uid);
+                    }
                 }
                 config
             }
             _ => GraceChatConfig::new(api_key, welcome, theme), // Default HTTP
         };
 
+        if let Some(token) = auth_token {
+            config.set_auth_token(token);
+        }
+        if let Some(room) = room {
+            config.set_room(room)?;
+        }
+        if let Some(stream_url) = stream_url {
+            config.set_stream_url(stream_url);
+        }
+        if let Some(bot_name) = bot_name {
+            config.set_bot_name(bot_name);
+        }
+        if let Some(message_pack) = message_pack {
+            config.set_message_pack(message_pack == "true");
+        }
+        if let Some(edit_window) = edit_window {
+            match edit_window.parse::<u32>() {
+                Ok(secs) => config.set_edit_window(secs),
+                Err(_) => web_sys::console::log_1(&format!("Invalid edit-window attribute: {}", edit_window).into()),
+            }
+        }
+
         self.config = Some(config);
         Ok(())
     }
 
     fn init_chat(&mut self) -> Result<(), JsValue> {
-        if let Some(config) = &self.config {
+        // Un único transporte para toda la vida del widget
+        self.transport = self.config.as_ref().map(|config| config.build_transport());
+        
+        if let (Some(config), Some(transport)) = (&self.config, self.transport.clone()) {
             // Inyectar estilos
             self.inject_styles()?;
             
@@ -80,6 +151,41 @@ impl GraceChatElement {
             // Validar API Key de forma asíncrona
             self.validate_and_show_chat();
             
+            // Abrir la conexión en tiempo real sin esperar al primer mensaje
+            self.setup_read_receipts(&transport)?;
+            
+            let element = self.element.clone();
+            let own_user_id = config.user_id.clone();
+            let transport_ref: Weak<dyn ChatTransport> = Rc::downgrade(&transport);
+            transport.subscribe(Box::new(move |event| {
+                let Some(transport) = transport_ref.upgrade() else { return };
+                match event {
+                    TransportEvent::Message(message) => {
+                        // Un servidor que reenvía a todos también nos devuelve lo nuestro
+                        let from_self = own_user_id.is_some() && message.sender_id() == own_user_id.as_deref();
+                        if !from_self {
+                            Self::handle_server_frame(&element, transport.as_ref(), message);
+                            Self::send_read_receipts(&element, transport.as_ref());
+                        }
+                    }
+                    TransportEvent::DeliveryStatus { message_id, status } => {
+                        Self::set_delivery_status(&element, &message_id, &status);
+                    }
+                    TransportEvent::TransportChanged(name) => Self::set_transport_label(&element, name),
+                    TransportEvent::StateChanged(_) => {}
+                }
+            }));
+            
+            let element = self.element.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match transport.connect().await {
+                    Ok(()) => Self::set_transport_label(&element, transport.name()),
+                    Err(e) => {
+                        web_sys::console::log_1(&format!("{} connection failed: {:?}", transport.name(), e).into());
+                    }
+                }
+            });
+            
             self.initialized = true;
         }
         Ok(())
@@ -103,7 +209,7 @@ impl GraceChatElement {
 :root{--grace-primary-color:#007bff;--grace-success-color:#28a745;--grace-error-color:#dc3545;--grace-dark-bg:#2c3e50;--grace-dark-text:#ecf0f1;--grace-light-bg:#ffffff;--grace-light-text:#333333;--grace-border-radius:12px;--grace-shadow:0 4px 20px rgba(0,0,0,0.15);--grace-animation-duration:0.3s;--grace-z-index:999999}
 .grace-chat-container{position:fixed;bottom:20px;right:20px;width:350px;max-width:calc(100vw - 40px);max-height:calc(100vh - 40px);border-radius:var(--grace-border-radius);box-shadow:var(--grace-shadow);font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',Roboto,'Helvetica Neue',Arial,sans-serif;z-index:var(--grace-z-index);overflow:hidden;transition:all var(--grace-animation-duration) ease-in-out;background-color:var(--grace-light-bg);color:var(--grace-light-text);border:1px solid #e1e8ed}
 .grace-chat-container--minimized{height:60px}
-.grace-chat-container--minimized .grace-chat-body,.grace-chat-container--minimized .grace-chat-status,.grace-chat-container--minimized .grace-chat-input-container{display:none}
+.grace-chat-container--minimized .grace-chat-body,.grace-chat-container--minimized .grace-chat-handoff,.grace-chat-container--minimized .grace-chat-status,.grace-chat-container--minimized .grace-chat-input-container{display:none}
 .grace-chat-container--minimized .grace-chat-minimize{transform:rotate(180deg)}
 .grace-chat-container.grace-chat--dark{background-color:var(--grace-dark-bg);color:var(--grace-dark-text);border-color:#34495e}
 .grace-chat-container.grace-chat--dark .grace-chat-header{background-color:#263544}
@@ -111,6 +217,12 @@ impl GraceChatElement {
 .grace-chat-header{display:flex;align-items:center;justify-content:space-between;padding:15px 20px;background-color:var(--grace-primary-color);color:white;font-weight:600}
 .grace-chat--dark .grace-chat-header{background-color:#263544}
 .grace-chat-title{font-size:clamp(14px,2.5vw,16px);line-height:1.4;font-weight:600}
+.grace-chat-header-info{display:flex;align-items:center;gap:8px;min-width:0}
+.grace-chat-header-avatar{width:28px;height:28px;border-radius:50%;object-fit:cover;background-color:rgba(255,255,255,0.2)}
+.grace-chat-handoff{display:flex;align-items:center;gap:8px;padding:8px 20px;font-size:12px;background-color:#fff3cd;color:#856404}
+.grace-chat-handoff[hidden]{display:none}
+.grace-chat-handoff::before{content:'';width:10px;height:10px;border-radius:50%;border:2px solid currentColor;border-top-color:transparent;animation:graceSpin 1s linear infinite}
+.grace-chat--dark .grace-chat-handoff{background-color:#664d03;color:#ffda6a}
 .grace-chat-minimize{background:none;border:none;color:white;font-size:20px;font-weight:bold;cursor:pointer;padding:5px 8px;border-radius:4px;transition:all 0.2s ease}
 .grace-chat-minimize:hover{background-color:rgba(255,255,255,0.1)}
 .grace-chat-minimize:focus{outline:2px solid rgba(255,255,255,0.3);outline-offset:2px}
@@ -136,6 +248,24 @@ impl GraceChatElement {
 .grace-chat-send-btn:hover{background-color:#0056b3;transform:scale(1.05)}
 .grace-chat-send-btn:active{transform:scale(0.95)}
 .grace-chat-send-btn:disabled{background-color:#6c757d;cursor:not-allowed;transform:none}
+.grace-chat-message--pending .grace-chat-message-content{opacity:0.6}
+.grace-chat-message--failed .grace-chat-message-content{background-color:var(--grace-error-color)!important}
+.grace-chat-message--user.grace-chat-message--sent .grace-chat-message-content::after,.grace-chat-message--user.grace-chat-message--acknowledged .grace-chat-message-content::after{content:'✓';margin-left:6px;font-size:11px;opacity:0.7}
+.grace-chat-message--user.grace-chat-message--delivered .grace-chat-message-content::after{content:'✓✓';margin-left:6px;font-size:11px;letter-spacing:-3px;opacity:0.7}
+.grace-chat-message--user.grace-chat-message--read .grace-chat-message-content::after{content:'✓✓';margin-left:6px;font-size:11px;letter-spacing:-3px;color:#8fd3ff}
+.grace-chat-message--streaming .grace-chat-message-content::after{content:'▍';margin-left:2px;animation:graceBlink 1s steps(2) infinite}
+.grace-chat-message-actions{display:none;justify-content:flex-end;gap:4px;margin-top:2px}
+.grace-chat-message--user:hover .grace-chat-message-actions,.grace-chat-message--user:focus-within .grace-chat-message-actions{display:flex}
+.grace-chat-message-actions button{background:none;border:none;cursor:pointer;font-size:12px;opacity:0.6;padding:2px 4px;color:inherit}
+.grace-chat-message-actions button:hover{opacity:1}
+.grace-chat-message--edited::after{content:'editado';display:block;font-size:11px;opacity:0.6;margin-top:2px}
+.grace-chat-message--deleted .grace-chat-message-content{font-style:italic;opacity:0.6}
+.grace-chat-message--bot.grace-chat-message--agent .grace-chat-message-content{background-color:#e8f5e9;border-left:3px solid #43a047}
+.grace-chat-container.grace-chat--dark .grace-chat-message--agent .grace-chat-message-content{background-color:#1e4620}
+.grace-chat-message-sender{display:flex;align-items:center;gap:6px;font-size:12px;font-weight:600;margin-bottom:4px;opacity:0.8}
+.grace-chat-message-avatar{width:20px;height:20px;border-radius:50%;object-fit:cover}
+.grace-chat-message--system{text-align:center;font-size:12px;opacity:0.7;margin:8px 0}
+.grace-chat-input-container--editing .grace-chat-input{border-color:var(--grace-primary-color)}
 .grace-chat-typing-indicator{font-size:12px;color:#6c757d;font-style:italic;padding:10px 0;animation:fadeInUp var(--grace-animation-duration) ease-out}
 .grace-chat-status{padding:10px 20px;border-top:1px solid #e1e8ed;font-size:12px;text-align:center;transition:all var(--grace-animation-duration) ease}
 .grace-chat--dark .grace-chat-status{border-top-color:#34495e}
@@ -144,7 +274,10 @@ impl GraceChatElement {
 .grace-chat-status--error{background-color:#f8d7da;color:#721c24}
 .grace-chat--dark .grace-chat-status--error{background-color:#58151c;color:#ea868f}
 .grace-chat-status-text{font-weight:500}
+.grace-chat-status-transport{margin-left:6px;opacity:.75}.grace-chat-status-transport:empty{display:none}
 @keyframes fadeInUp{from{opacity:0;transform:translateY(10px)}to{opacity:1;transform:translateY(0)}}
+@keyframes graceBlink{to{visibility:hidden}}
+@keyframes graceSpin{to{transform:rotate(360deg)}}
 @media (max-width:480px){.grace-chat-container{width:calc(100vw - 20px);bottom:10px;right:10px;left:10px}.grace-chat-container--minimized{height:50px}.grace-chat-header{padding:12px 15px}.grace-chat-body{padding:15px;min-height:150px;max-height:calc(100vh - 200px)}.grace-chat-title{font-size:14px}.grace-chat-input-container{padding:12px 15px}}
 @media (max-width:320px){.grace-chat-container{width:calc(100vw - 10px);bottom:5px;right:5px;left:5px}.grace-chat-message-content{font-size:13px!important;padding:10px 12px}.grace-chat-input{font-size:13px}.grace-chat-send-btn{min-width:40px;font-size:14px}}
 @media (prefers-reduced-motion:reduce){.grace-chat-container,.grace-chat-message,.grace-chat-minimize,.grace-chat-send-btn{animation:none;transition:none}}
@@ -163,9 +296,15 @@ impl GraceChatElement {
             r#"
             <div class="grace-chat-container {}">
                 <div class="grace-chat-header">
-                    <span class="grace-chat-title">Grace</span>
+                    <div class="grace-chat-header-info">
+                        <img class="grace-chat-header-avatar" alt="" hidden />
+                        <span class="grace-chat-title"></span>
+                    </div>
                     <button class="grace-chat-minimize" type="button">−</button>
                 </div>
+                <div class="grace-chat-handoff" hidden>
+                    <span class="grace-chat-handoff-text"></span>
+                </div>
                 <div class="grace-chat-body">
                     <div class="grace-chat-messages">
                         <div class="grace-chat-message grace-chat-message--bot">
@@ -188,6 +327,7 @@ impl GraceChatElement {
                 </div>
                 <div class="grace-chat-status">
                     <span class="grace-chat-status-text">Validando...</span>
+                    <span class="grace-chat-status-transport"></span>
                 </div>
             </div>
             "#,
@@ -196,6 +336,13 @@ impl GraceChatElement {
         );
 
         self.element.set_inner_html(&html);
+        
+        // El nombre del bot se guarda para volver a él cuando se va el agente
+        if let Some(container) = self.element.query_selector(".grace-chat-container")? {
+            container.set_attribute("data-bot-name", &config.bot_name)?;
+        }
+        Self::set_header(&self.element, &config.bot_name, None);
+        
         self.setup_event_listeners()?;
         
         Ok(())
@@ -224,6 +371,7 @@ impl GraceChatElement {
 
         // Setup para el envío de mensajes
         self.setup_message_sending()?;
+        self.setup_message_editing()?;
         
         Ok(())
     }
@@ -235,10 +383,17 @@ impl GraceChatElement {
         if let (Some(btn), Some(input_elem)) = (send_btn, input) {
             let element_clone = self.element.clone();
             let config_clone = self.config.clone();
+            let transport_clone = self.transport.clone();
+            
+            // Avisos de escritura hacia el servidor
+            let typing_notifier = self.transport.clone().map(TypingNotifier::new);
+            let typing_for_click = typing_notifier.clone();
+            let typing_for_keypress = typing_notifier.clone();
             
             // Clonar el input element para usarlo en ambos closures
             let input_for_click = input_elem.clone();
             let input_for_keypress = input_elem.clone();
+            let input_for_typing = input_elem.clone();
             
             // Manejar click del botón enviar
             let click_closure = Closure::wrap(Box::new(move |_: Event| {
@@ -248,10 +403,13 @@ impl GraceChatElement {
                 if !message.is_empty() {
                     // Limpiar input
                     input_element.set_value("");
+                    if let Some(notifier) = &typing_for_click {
+                        notifier.stop();
+                    }
                     
                     // Enviar mensaje
-                    if let Some(config) = &config_clone {
-                        Self::send_message_static(element_clone.clone(), config.clone(), message);
+                    if let (Some(config), Some(transport)) = (&config_clone, &transport_clone) {
+                        Self::submit_message(element_clone.clone(), config, transport.clone(), message);
                     }
                 }
             }) as Box<dyn FnMut(_)>);
@@ -262,6 +420,7 @@ impl GraceChatElement {
             // Manejar Enter en el input
             let element_clone2 = self.element.clone();
             let config_clone2 = self.config.clone();
+            let transport_clone2 = self.transport.clone();
             
             let keypress_closure = Closure::wrap(Box::new(move |event: Event| {
                 let keyboard_event = event.dyn_ref::<KeyboardEvent>().unwrap();
@@ -271,9 +430,12 @@ impl GraceChatElement {
                     
                     if !message.is_empty() {
                         input_element.set_value("");
+                        if let Some(notifier) = &typing_for_keypress {
+                            notifier.stop();
+                        }
                         
-                        if let Some(config) = &config_clone2 {
-                            Self::send_message_static(element_clone2.clone(), config.clone(), message);
+                        if let (Some(config), Some(transport)) = (&config_clone2, &transport_clone2) {
+                            Self::submit_message(element_clone2.clone(), config, transport.clone(), message);
                         }
                     }
                 }
@@ -281,30 +443,256 @@ impl GraceChatElement {
             
             input_elem.add_event_listener_with_callback("keypress", keypress_closure.as_ref().unchecked_ref())?;
             keypress_closure.forget();
+            
+            // Avisar al teclear y al salir del input
+            if let Some(notifier) = typing_notifier {
+                let notifier_for_input = notifier.clone();
+                let input_closure = Closure::wrap(Box::new(move |_: Event| {
+                    let input_element = input_for_typing.dyn_ref::<HtmlInputElement>().unwrap();
+                    notifier_for_input.on_input(input_element.value().trim().is_empty());
+                }) as Box<dyn FnMut(_)>);
+                
+                input_elem.add_event_listener_with_callback("input", input_closure.as_ref().unchecked_ref())?;
+                input_closure.forget();
+                
+                let blur_closure = Closure::wrap(Box::new(move |_: Event| {
+                    notifier.stop();
+                }) as Box<dyn FnMut(_)>);
+                
+                input_elem.add_event_listener_with_callback("blur", blur_closure.as_ref().unchecked_ref())?;
+                blur_closure.forget();
+            }
         }
         
         Ok(())
     }
 
-    fn send_message_static(element: HtmlElement, config: GraceChatConfig, message: String) {
-        // Agregar mensaje del usuario
-        Self::add_message_to_chat(&element, &message, true);
+    // Editar o retirar mensajes propios con los botones de su burbuja
+    fn setup_message_editing(&self) -> Result<(), JsValue> {
+        let (Some(config), Some(transport)) = (self.config.clone(), self.transport.clone()) else { return Ok(()) };
+        if !config.can_edit_messages() {
+            return Ok(());
+        }
+        
+        let messages_container = self.element.query_selector(".grace-chat-messages")?;
+        let input = self.element.query_selector(".grace-chat-input")?;
+        
+        if let (Some(container), Some(input_elem)) = (messages_container, input) {
+            let element_clone = self.element.clone();
+            let input_for_click = input_elem.clone();
+            
+            // Un único listener para todas las burbujas, incluidas las que se agreguen después
+            let click_closure = Closure::wrap(Box::new(move |event: Event| {
+                let Some(target) = event.target().and_then(|target| target.dyn_into::<Element>().ok()) else { return };
+                let is_edit = matches!(target.closest(".grace-chat-message-edit"), Ok(Some(_)));
+                let is_delete = matches!(target.closest(".grace-chat-message-delete"), Ok(Some(_)));
+                if !is_edit && !is_delete {
+                    return;
+                }
+                
+                let Ok(Some(message_div)) = target.closest(".grace-chat-message") else { return };
+                let Some(message_id) = message_div.get_attribute("data-message-id") else { return };
+                
+                if !Self::within_edit_window(&message_div, config.edit_window_secs) {
+                    Self::remove_message_actions(&message_div);
+                    return;
+                }
+                
+                if is_edit {
+                    Self::start_editing(&element_clone, &input_for_click, &message_div, &message_id);
+                    return;
+                }
+                
+                match transport.delete_message(&message_id) {
+                    Ok(()) => Self::mark_deleted(&message_div),
+                    Err(e) => {
+                        web_sys::console::log_1(&format!("Failed to delete message: {:?}", e).into());
+                        Self::add_message_to_chat(&element_clone, "No se pudo eliminar el mensaje", false);
+                    }
+                }
+            }) as Box<dyn FnMut(_)>);
+            
+            container.add_event_listener_with_callback("click", click_closure.as_ref().unchecked_ref())?;
+            click_closure.forget();
+            
+            // Escape cancela la edición en curso
+            let element_clone = self.element.clone();
+            let input_for_keydown = input_elem.clone();
+            let keydown_closure = Closure::wrap(Box::new(move |event: Event| {
+                let keyboard_event = event.dyn_ref::<KeyboardEvent>().unwrap();
+                if keyboard_event.key() == "Escape" && Self::take_editing_id(&element_clone).is_some() {
+                    input_for_keydown.dyn_ref::<HtmlInputElement>().unwrap().set_value("");
+                }
+            }) as Box<dyn FnMut(_)>);
+            
+            input_elem.add_event_listener_with_callback("keydown", keydown_closure.as_ref().unchecked_ref())?;
+            keydown_closure.forget();
+        }
+        
+        Ok(())
+    }
+
+    // Enviar lo escrito: un mensaje nuevo o la corrección del que se está editando
+    fn submit_message(element: HtmlElement, config: &GraceChatConfig, transport: Rc<dyn ChatTransport>, message: String) {
+        match Self::take_editing_id(&element) {
+            Some(message_id) => Self::edit_message_static(&element, config, transport.as_ref(), &message_id, &message),
+            None => Self::send_message_static(element, config, transport, message),
+        }
+    }
+
+    // Pasar el texto de la burbuja al input para corregirlo
+    fn start_editing(element: &HtmlElement, input: &Element, message_div: &Element, message_id: &str) {
+        let Ok(Some(input_container)) = element.query_selector(".grace-chat-input-container") else { return };
+        let _ = input_container.set_attribute("data-editing-id", message_id);
+        let _ = input_container.class_list().add_1("grace-chat-input-container--editing");
+        
+        let text = message_div.query_selector(".grace-chat-message-content").ok().flatten()
+            .and_then(|content_div| content_div.text_content())
+            .unwrap_or_default();
+        let input_element = input.dyn_ref::<HtmlInputElement>().unwrap();
+        input_element.set_value(&text);
+        let _ = input_element.focus();
+    }
+
+    // Terminar la edición en curso, devolviendo el id del mensaje editado
+    fn take_editing_id(element: &HtmlElement) -> Option<String> {
+        let input_container = element.query_selector(".grace-chat-input-container").ok().flatten()?;
+        let message_id = input_container.get_attribute("data-editing-id")?;
+        let _ = input_container.remove_attribute("data-editing-id");
+        let _ = input_container.class_list().remove_1("grace-chat-input-container--editing");
+        Some(message_id)
+    }
+
+    fn edit_message_static(element: &HtmlElement, config: &GraceChatConfig, transport: &dyn ChatTransport, message_id: &str, content: &str) {
+        let Some(message_div) = Self::find_message(element, message_id) else { return };
+        if message_div.class_list().contains("grace-chat-message--deleted") {
+            return;
+        }
+        
+        let current = message_div.query_selector(".grace-chat-message-content").ok().flatten()
+            .and_then(|content_div| content_div.text_content())
+            .unwrap_or_default();
+        if current == content {
+            return;
+        }
+        
+        // El plazo pudo cerrarse mientras se editaba
+        if !Self::within_edit_window(&message_div, config.edit_window_secs) {
+            Self::remove_message_actions(&message_div);
+            Self::add_message_to_chat(element, "Ya no se puede editar este mensaje", false);
+            return;
+        }
+        
+        match transport.edit_message(message_id, content) {
+            Ok(()) => Self::mark_edited(&message_div, content),
+            Err(e) => {
+                web_sys::console::log_1(&format!("Failed to edit message: {:?}", e).into());
+                Self::add_message_to_chat(element, "No se pudo editar el mensaje", false);
+            }
+        }
+    }
+
+    // Botones de editar y eliminar, que desaparecen al cerrarse el plazo
+    fn add_message_actions(message_div: &Element, window_secs: u32) {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let _ = message_div.set_attribute("data-sent-at", &js_sys::Date::now().to_string());
+        
+        let actions = document.create_element("div").unwrap();
+        actions.set_class_name("grace-chat-message-actions");
+        actions.set_inner_html(r#"<button class="grace-chat-message-edit" title="Editar">✎</button><button class="grace-chat-message-delete" title="Eliminar">✕</button>"#);
+        message_div.append_child(&actions).unwrap();
+        
+        let expire_callback = Closure::once_into_js(move || actions.remove());
+        set_timer(&expire_callback, window_secs.saturating_mul(1000));
+    }
+
+    fn remove_message_actions(message_div: &Element) {
+        if let Ok(Some(actions)) = message_div.query_selector(".grace-chat-message-actions") {
+            actions.remove();
+        }
+    }
+
+    fn within_edit_window(message_div: &Element, window_secs: u32) -> bool {
+        message_div.get_attribute("data-sent-at")
+            .and_then(|sent_at| sent_at.parse::<f64>().ok())
+            .is_some_and(|sent_at| js_sys::Date::now() - sent_at < f64::from(window_secs) * 1000.0)
+    }
+
+    // Burbuja de un mensaje propio o recibido, por su id
+    fn find_message(element: &HtmlElement, message_id: &str) -> Option<Element> {
+        let selector = format!(
+            "{},{}",
+            Self::attribute_selector(".grace-chat-message", "data-message-id", message_id),
+            Self::attribute_selector(".grace-chat-message", "data-receipt-id", message_id)
+        );
+        element.query_selector(&selector).ok().flatten()
+    }
+
+    fn mark_edited(message_div: &Element, content: &str) {
+        Self::set_bubble_text(message_div, content);
+        let _ = message_div.class_list().add_1("grace-chat-message--edited");
+    }
+
+    fn mark_deleted(message_div: &Element) {
+        Self::set_bubble_text(message_div, "Mensaje eliminado");
+        Self::remove_message_actions(message_div);
+        let class_list = message_div.class_list();
+        let _ = class_list.remove_1("grace-chat-message--edited");
+        let _ = class_list.add_1("grace-chat-message--deleted");
+    }
+
+    fn send_message_static(element: HtmlElement, config: &GraceChatConfig, transport: Rc<dyn ChatTransport>, message: String) {
+        // Agregar mensaje del usuario, identificado para seguir su entrega
+        let message_id = config.new_message_id();
+        if let Some(message_div) = Self::add_message_to_chat(&element, &message, true) {
+            let _ = message_div.set_attribute("data-message-id", &message_id);
+            if config.can_edit_messages() {
+                Self::add_message_actions(&message_div, config.edit_window_secs);
+            }
+        }
         
         // Mostrar typing indicator
         Self::show_typing_indicator(&element);
         
         // Procesar mensaje de forma asíncrona
         wasm_bindgen_futures::spawn_local(async move {
-            match config.process_chat_message(&message).await {
-                Ok(response) => {
+            // Respuesta HTTP en streaming: la burbuja crece con cada parte
+            let stream_id = format!("{}-reply", message_id);
+            let mut on_delta = {
+                let element = element.clone();
+                let message_id = message_id.clone();
+                move |delta: &str| {
+                    Self::start_stream_bubble(&element, &stream_id, Some(&message_id));
+                    Self::append_stream_delta(&element, &stream_id, delta);
+                }
+            };
+            
+            match transport.send(&message_id, &message, &mut on_delta).await {
+                Ok(reply) => {
                     // Ocultar typing indicator
                     Self::hide_typing_indicator(&element);
                     
-                    // Agregar respuesta del bot
-                    Self::add_message_to_chat(&element, &response, false);
+                    // Si la respuesta llegó en streaming ya tiene burbuja: la respuesta
+                    // final es la que cuenta. Si no, agregar respuesta del bot.
+                    let selector = Self::attribute_selector(".grace-chat-message", "data-reply-to", &message_id);
+                    if let Ok(Some(message_div)) = element.query_selector(&selector) {
+                        if !reply.text.is_empty() {
+                            Self::set_bubble_text(&message_div, &reply.text);
+                        }
+                        let _ = message_div.class_list().remove_1("grace-chat-message--streaming");
+                    } else if let Some(message_div) = Self::add_message_to_chat(&element, &reply.text, false) {
+                        // Solo se acusa una respuesta con id propio: el del mensaje
+                        // del usuario no identifica a la respuesta
+                        if let Some(reply_id) = &reply.message_id {
+                            Self::track_receipts(&message_div, transport.as_ref(), reply_id);
+                            Self::send_read_receipts(&element, transport.as_ref());
+                        }
+                    }
                 }
                 Err(error) => {
                     Self::hide_typing_indicator(&element);
+                    // Una respuesta que se cortó a medias deja de mostrarse como en curso
+                    Self::finish_stream_bubble(&element, &format!("{}-reply", message_id));
                     
                     let error_msg = error.as_string().unwrap_or("Error procesando mensaje".to_string());
                     Self::add_message_to_chat(&element, &error_msg, false);
@@ -315,7 +703,7 @@ impl GraceChatElement {
         });
     }
 
-    fn add_message_to_chat(element: &HtmlElement, message: &str, is_user: bool) {
+    fn add_message_to_chat(element: &HtmlElement, message: &str, is_user: bool) -> Option<Element> {
         let window = web_sys::window().unwrap();
         let document = window.document().unwrap();
         
@@ -333,6 +721,365 @@ impl GraceChatElement {
             
             // Scroll hacia abajo
             messages_container.set_scroll_top(messages_container.scroll_height());
+            
+            Some(message_div)
+        } else {
+            None
+        }
+    }
+
+    // Frames del servidor que no son respuesta directa a un mensaje
+    fn handle_server_frame(element: &HtmlElement, transport: &dyn ChatTransport, message: WebSocketMessage) {
+        match message {
+            WebSocketMessage::MessageStart { message_id, reply_to } => {
+                if let Some(message_div) = Self::start_stream_bubble(element, &message_id, reply_to.as_deref()) {
+                    Self::track_receipts(&message_div, transport, &message_id);
+                }
+            }
+            WebSocketMessage::MessageDelta { message_id, delta } => {
+                Self::append_stream_delta(element, &message_id, &delta);
+            }
+            WebSocketMessage::MessageEnd { message_id } => {
+                Self::finish_stream_bubble(element, &message_id);
+            }
+            WebSocketMessage::UserTyping { user_id, is_typing } => {
+                Self::set_remote_typing(element, &user_id, is_typing);
+            }
+            // Mensaje de un agente u otro participante. Quien lo envía ya terminó de escribirlo.
+            WebSocketMessage::ChatMessage { id, content, user_id, .. } | WebSocketMessage::RoomMessage { id, content, user_id, .. } => {
+                Self::set_remote_typing(element, &user_id, false);
+                if let Some(message_div) = Self::add_message_to_chat(element, &content, false) {
+                    Self::mark_agent_message(element, &message_div, &user_id);
+                    Self::track_receipts(&message_div, transport, &id);
+                }
+            }
+            // Traspaso a una persona
+            WebSocketMessage::HandoffRequested { queue_position, .. } => {
+                Self::show_handoff(element, queue_position);
+            }
+            WebSocketMessage::AgentJoined { agent_id, agent_name, avatar_url } => {
+                Self::agent_joined(element, &agent_id, &agent_name, avatar_url.as_deref());
+            }
+            WebSocketMessage::AgentLeft { agent_id, agent_name, .. } => {
+                Self::agent_left(element, &agent_id, &agent_name);
+            }
+            // Acuses del otro lado sobre los mensajes del usuario
+            WebSocketMessage::MessageDelivered { message_id, .. } => {
+                Self::set_delivery_status(element, &message_id, &DeliveryStatus::Delivered);
+            }
+            WebSocketMessage::MessageRead { message_id, .. } => {
+                Self::set_delivery_status(element, &message_id, &DeliveryStatus::Read);
+            }
+            // Ediciones y retiradas del otro lado: solo se corrige la burbuja afectada
+            WebSocketMessage::MessageEdited { message_id, content, .. } => {
+                if let Some(message_div) = Self::find_message(element, &message_id) {
+                    Self::mark_edited(&message_div, &content);
+                }
+            }
+            WebSocketMessage::MessageDeleted { message_id, .. } => {
+                if let Some(message_div) = Self::find_message(element, &message_id) {
+                    Self::mark_deleted(&message_div);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Nombre y avatar de quien atiende: el bot o el agente
+    fn set_header(element: &HtmlElement, name: &str, avatar_url: Option<&str>) {
+        if let Ok(Some(title)) = element.query_selector(".grace-chat-title") {
+            title.set_text_content(Some(name));
+        }
+        if let Ok(Some(avatar)) = element.query_selector(".grace-chat-header-avatar") {
+            match avatar_url {
+                Some(url) => {
+                    let _ = avatar.set_attribute("src", url);
+                    let _ = avatar.remove_attribute("hidden");
+                }
+                None => {
+                    let _ = avatar.remove_attribute("src");
+                    let _ = avatar.set_attribute("hidden", "");
+                }
+            }
+        }
+    }
+
+    // Mientras se espera a un agente, avisar y mostrar la posición en la cola
+    fn show_handoff(element: &HtmlElement, queue_position: Option<u32>) {
+        let Ok(Some(handoff)) = element.query_selector(".grace-chat-handoff") else { return };
+        
+        let text = match queue_position {
+            Some(position) if position > 0 => format!("Conectando con un agente… Posición en la cola: {}", position),
+            _ => "Conectando con un agente…".to_string(),
+        };
+        if let Ok(Some(text_elem)) = handoff.query_selector(".grace-chat-handoff-text") {
+            text_elem.set_text_content(Some(&text));
+        }
+        let _ = handoff.remove_attribute("hidden");
+    }
+
+    fn hide_handoff(element: &HtmlElement) {
+        if let Ok(Some(handoff)) = element.query_selector(".grace-chat-handoff") {
+            let _ = handoff.set_attribute("hidden", "");
+        }
+    }
+
+    // El agente pasa a atender: su nombre va a la cabecera y sus mensajes se
+    // distinguen de los del bot
+    fn agent_joined(element: &HtmlElement, agent_id: &str, agent_name: &str, avatar_url: Option<&str>) {
+        Self::hide_handoff(element);
+        
+        let Ok(Some(container)) = element.query_selector(".grace-chat-container") else { return };
+        let _ = container.set_attribute("data-agent-id", agent_id);
+        let _ = container.set_attribute("data-agent-name", agent_name);
+        match avatar_url {
+            Some(url) => { let _ = container.set_attribute("data-agent-avatar", url); }
+            None => { let _ = container.remove_attribute("data-agent-avatar"); }
+        }
+        
+        Self::set_header(element, agent_name, avatar_url);
+        Self::add_system_message(element, &format!("{} se unió a la conversación", agent_name));
+    }
+
+    // Al irse el agente vuelve a atender el bot
+    fn agent_left(element: &HtmlElement, agent_id: &str, agent_name: &str) {
+        Self::hide_handoff(element);
+        
+        let Ok(Some(container)) = element.query_selector(".grace-chat-container") else { return };
+        if container.get_attribute("data-agent-id").as_deref() != Some(agent_id) {
+            return;
+        }
+        let _ = container.remove_attribute("data-agent-id");
+        let _ = container.remove_attribute("data-agent-name");
+        let _ = container.remove_attribute("data-agent-avatar");
+        
+        let bot_name = container.get_attribute("data-bot-name").unwrap_or_default();
+        Self::set_header(element, &bot_name, None);
+        Self::add_system_message(element, &format!("{} salió de la conversación", agent_name));
+    }
+
+    // Agente que atiende ahora: (id, nombre, avatar)
+    fn current_agent(element: &HtmlElement) -> Option<(String, String, Option<String>)> {
+        let container = element.query_selector(".grace-chat-container").ok().flatten()?;
+        let agent_id = container.get_attribute("data-agent-id")?;
+        let agent_name = container.get_attribute("data-agent-name").unwrap_or_else(|| agent_id.clone());
+        Some((agent_id, agent_name, container.get_attribute("data-agent-avatar")))
+    }
+
+    // Los mensajes del agente llevan su nombre y avatar y otro estilo que los del bot
+    fn mark_agent_message(element: &HtmlElement, message_div: &Element, user_id: &str) {
+        let Some((agent_id, agent_name, avatar_url)) = Self::current_agent(element) else { return };
+        if agent_id != user_id {
+            return;
+        }
+        
+        let document = web_sys::window().unwrap().document().unwrap();
+        let sender = document.create_element("div").unwrap();
+        sender.set_class_name("grace-chat-message-sender");
+        
+        if let Some(url) = avatar_url {
+            let avatar = document.create_element("img").unwrap();
+            avatar.set_class_name("grace-chat-message-avatar");
+            let _ = avatar.set_attribute("src", &url);
+            let _ = avatar.set_attribute("alt", "");
+            let _ = sender.append_child(&avatar);
+        }
+        
+        let name = document.create_element("span").unwrap();
+        name.set_text_content(Some(&agent_name));
+        let _ = sender.append_child(&name);
+        
+        let _ = message_div.insert_before(&sender, message_div.first_child().as_ref());
+        let _ = message_div.class_list().add_1("grace-chat-message--agent");
+    }
+
+    // Aviso centrado que no es de nadie (entrada y salida de agentes)
+    fn add_system_message(element: &HtmlElement, text: &str) {
+        if let Some(message_div) = Self::add_message_to_chat(element, text, false) {
+            message_div.set_class_name("grace-chat-message grace-chat-message--system");
+        }
+    }
+
+    // Acusar recibo de un mensaje recibido y marcarlo para el acuse de lectura
+    fn track_receipts(message_div: &Element, transport: &dyn ChatTransport, message_id: &str) {
+        if message_div.has_attribute("data-receipt-id") {
+            return;
+        }
+        let _ = message_div.set_attribute("data-receipt-id", message_id);
+        transport.send_receipt(message_id, Receipt::Delivered);
+    }
+
+    // Acusar lectura de los mensajes recibidos que están a la vista: con el
+    // widget abierto, la página visible y la burbuja dentro del área de mensajes
+    fn send_read_receipts(element: &HtmlElement, transport: &dyn ChatTransport) {
+        let document = web_sys::window().unwrap().document().unwrap();
+        if document.hidden() {
+            return;
+        }
+        
+        let Ok(Some(container)) = element.query_selector(".grace-chat-container") else { return };
+        if container.class_list().contains("grace-chat-container--minimized") {
+            return;
+        }
+        
+        let Ok(Some(body)) = element.query_selector(".grace-chat-body") else { return };
+        let view = body.get_bounding_client_rect();
+        
+        let Ok(unread) = element.query_selector_all(".grace-chat-message[data-receipt-id]:not([data-read])") else { return };
+        for index in 0..unread.length() {
+            let Some(message_div) = unread.item(index).and_then(|node| node.dyn_into::<Element>().ok()) else { continue };
+            
+            let rect = message_div.get_bounding_client_rect();
+            if rect.bottom() <= view.top() || rect.top() >= view.bottom() {
+                continue;
+            }
+            
+            if let Some(message_id) = message_div.get_attribute("data-receipt-id") {
+                transport.send_receipt(&message_id, Receipt::Read);
+                let _ = message_div.set_attribute("data-read", "");
+            }
+        }
+    }
+
+    // Volver a mirar qué se ve cuando cambia la vista: scroll, abrir el widget
+    // o volver a la pestaña
+    fn setup_read_receipts(&self, transport: &Rc<dyn ChatTransport>) -> Result<(), JsValue> {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let body = self.element.query_selector(".grace-chat-body")?;
+        let minimize_btn = self.element.query_selector(".grace-chat-minimize")?;
+        
+        let element = self.element.clone();
+        let transport_ref: Weak<dyn ChatTransport> = Rc::downgrade(transport);
+        let closure = Closure::wrap(Box::new(move |_: Event| {
+            if let Some(transport) = transport_ref.upgrade() {
+                Self::send_read_receipts(&element, transport.as_ref());
+            }
+        }) as Box<dyn FnMut(_)>);
+        
+        if let Some(body) = body {
+            body.add_event_listener_with_callback("scroll", closure.as_ref().unchecked_ref())?;
+        }
+        if let Some(btn) = minimize_btn {
+            btn.add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+        }
+        document.add_event_listener_with_callback("visibilitychange", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+        
+        Ok(())
+    }
+
+    // Selector de los elementos de `base` cuyo `attribute` vale `value`. Los ids
+    // llegan del servidor: se escapan para que no rompan ni amplíen el selector.
+    fn attribute_selector(base: &str, attribute: &str, value: &str) -> String {
+        format!("{}[{}=\"{}\"]", base, attribute, web_sys::css::escape(value))
+    }
+
+    // Crear la burbuja del bot que irá creciendo con cada parte de la respuesta
+    fn start_stream_bubble(element: &HtmlElement, stream_id: &str, reply_to: Option<&str>) -> Option<Element> {
+        let selector = Self::attribute_selector(".grace-chat-message", "data-stream-id", stream_id);
+        if let Ok(Some(message_div)) = element.query_selector(&selector) {
+            return Some(message_div);
+        }
+        
+        // La respuesta ya empezó: el indicador sobra
+        Self::hide_typing_indicator(element);
+        
+        let message_div = Self::add_message_to_chat(element, "", false)?;
+        let _ = message_div.set_attribute("data-stream-id", stream_id);
+        if let Some(reply_to) = reply_to {
+            let _ = message_div.set_attribute("data-reply-to", reply_to);
+        }
+        let _ = message_div.class_list().add_1("grace-chat-message--streaming");
+        Some(message_div)
+    }
+
+    fn append_stream_delta(element: &HtmlElement, stream_id: &str, delta: &str) {
+        let Some(message_div) = Self::start_stream_bubble(element, stream_id, None) else { return };
+        
+        if let Ok(Some(content_div)) = message_div.query_selector(".grace-chat-message-content") {
+            let text = content_div.text_content().unwrap_or_default();
+            content_div.set_text_content(Some(&format!("{}{}", text, delta)));
+        }
+        
+        // Seguir el final de la respuesta mientras crece
+        if let Ok(Some(messages_container)) = element.query_selector(".grace-chat-messages") {
+            messages_container.set_scroll_top(messages_container.scroll_height());
+        }
+    }
+
+    fn finish_stream_bubble(element: &HtmlElement, stream_id: &str) {
+        let selector = Self::attribute_selector(".grace-chat-message", "data-stream-id", stream_id);
+        if let Ok(Some(message_div)) = element.query_selector(&selector) {
+            let _ = message_div.class_list().remove_1("grace-chat-message--streaming");
+        }
+    }
+
+    fn set_bubble_text(message_div: &Element, text: &str) {
+        if let Ok(Some(content_div)) = message_div.query_selector(".grace-chat-message-content") {
+            content_div.set_text_content(Some(text));
+        }
+    }
+
+    // Reflejar el estado de entrega en la burbuja del usuario
+    fn set_delivery_status(element: &HtmlElement, message_id: &str, status: &DeliveryStatus) {
+        let selector = Self::attribute_selector(".grace-chat-message", "data-message-id", message_id);
+        let Ok(Some(message_div)) = element.query_selector(&selector) else { return };
+        
+        // Estados en el orden en que avanza la entrega
+        const PROGRESS: [&str; 5] = [
+            "grace-chat-message--pending",
+            "grace-chat-message--sent",
+            "grace-chat-message--acknowledged",
+            "grace-chat-message--delivered",
+            "grace-chat-message--read",
+        ];
+        
+        let status_class = match status {
+            DeliveryStatus::Pending => PROGRESS[0],
+            DeliveryStatus::Sent => PROGRESS[1],
+            DeliveryStatus::Acknowledged => PROGRESS[2],
+            DeliveryStatus::Delivered => PROGRESS[3],
+            DeliveryStatus::Read => PROGRESS[4],
+            DeliveryStatus::Failed(reason) => {
+                let _ = message_div.set_attribute("title", reason);
+                "grace-chat-message--failed"
+            }
+        };
+        
+        // Los avisos pueden llegar desordenados (un ack después del "visto"):
+        // el estado nunca retrocede
+        let class_list = message_div.class_list();
+        let current = PROGRESS.iter().rposition(|class| class_list.contains(class));
+        let new = PROGRESS.iter().position(|class| *class == status_class);
+        if let (Some(current), Some(new)) = (current, new) {
+            if new < current {
+                return;
+            }
+        }
+        
+        let _ = class_list.remove_6(
+            PROGRESS[0],
+            PROGRESS[1],
+            PROGRESS[2],
+            PROGRESS[3],
+            PROGRESS[4],
+            "grace-chat-message--failed",
+        );
+        let _ = class_list.add_1(status_class);
+    }
+
+    // Mostrar en la barra de estado por qué transporte va el chat
+    fn set_transport_label(element: &HtmlElement, name: &str) {
+        let label = match name {
+            "websocket" => "WebSocket",
+            "http" => "HTTP",
+            "sse" => "SSE",
+            "long-polling" => "Long-polling",
+            other => other,
+        };
+        
+        if let Ok(Some(transport_elem)) = element.query_selector(".grace-chat-status-transport") {
+            transport_elem.set_text_content(Some(&format!("· vía {}", label)));
         }
     }
 
@@ -357,6 +1104,53 @@ impl GraceChatElement {
         }
     }
 
+    // "X está escribiendo..." de otro participante; caduca solo si no llega el aviso de fin
+    fn set_remote_typing(element: &HtmlElement, user_id: &str, is_typing: bool) {
+        let selector = Self::attribute_selector(".grace-chat-typing-indicator", "data-typing-user", user_id);
+        let existing = element.query_selector(&selector).ok().flatten();
+        
+        // Cancelar la caducidad pendiente del indicador anterior
+        if let Some(typing_div) = &existing {
+            let timer = typing_div.get_attribute("data-expiry-timer").and_then(|id| id.parse().ok());
+            cancel_timer(timer);
+        }
+        
+        if !is_typing {
+            if let Some(typing_div) = existing {
+                typing_div.remove();
+            }
+            return;
+        }
+        
+        let typing_div = match existing {
+            Some(typing_div) => typing_div,
+            None => {
+                let Ok(Some(messages_container)) = element.query_selector(".grace-chat-messages") else { return };
+                let document = web_sys::window().unwrap().document().unwrap();
+                let typing_div = document.create_element("div").unwrap();
+                typing_div.set_class_name("grace-chat-typing-indicator grace-chat-typing-indicator--remote");
+                let _ = typing_div.set_attribute("data-typing-user", user_id);
+                // Del agente se muestra el nombre en lugar del id
+                let name = Self::current_agent(element)
+                    .filter(|(agent_id, _, _)| agent_id == user_id)
+                    .map_or_else(|| user_id.to_string(), |(_, agent_name, _)| agent_name);
+                typing_div.set_text_content(Some(&format!("{} está escribiendo...", name)));
+                
+                let _ = messages_container.append_child(&typing_div);
+                messages_container.set_scroll_top(messages_container.scroll_height());
+                typing_div
+            }
+        };
+        
+        let typing_for_expiry = typing_div.clone();
+        let expiry_callback = Closure::once_into_js(move || {
+            typing_for_expiry.remove();
+        });
+        if let Some(timer) = set_timer(&expiry_callback, REMOTE_TYPING_EXPIRY_MS) {
+            let _ = typing_div.set_attribute("data-expiry-timer", &timer.to_string());
+        }
+    }
+
     fn validate_and_show_chat(&self) {
         if let Some(config) = &self.config {
             let config_clone = GraceChatConfig::new(
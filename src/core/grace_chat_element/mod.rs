@@ -1 +1,2 @@
-pub mod grace_chat_element_model;
\ No newline at end of file
+pub mod grace_chat_element_model;
+pub mod typing_notifier;
\ No newline at end of file
@@ -0,0 +1,71 @@
+use wasm_bindgen::prelude::*;
+use std::cell::Cell;
+use std::rc::Rc;
+use crate::core::transport::chat_transport::ChatTransport;
+use crate::core::websocket::websocket_manager::{clear_timer, set_timer};
+
+// Sin teclear durante este tiempo, se avisa de que el usuario dejó de escribir
+const TYPING_STOP_DELAY_MS: u32 = 3_000;
+
+// Mientras se sigue escribiendo, el aviso se repite para que no caduque en el
+// otro lado (ver REMOTE_TYPING_EXPIRY_MS en el widget)
+const TYPING_REFRESH_MS: f64 = 4_000.0;
+
+#[derive(Debug)]
+struct NotifierInner {
+    transport: Rc<dyn ChatTransport>,
+    typing: Cell<bool>,
+    last_sent: Cell<f64>,
+    stop_timer: Cell<Option<i32>>,
+}
+
+// Avisos de escritura del usuario local con debounce: un "empieza" al teclear,
+// repetido cada pocos segundos, y un "termina" al parar, enviar o salir del input
+#[derive(Debug, Clone)]
+pub struct TypingNotifier {
+    inner: Rc<NotifierInner>,
+}
+
+impl TypingNotifier {
+    pub fn new(transport: Rc<dyn ChatTransport>) -> Self {
+        Self {
+            inner: Rc::new(NotifierInner {
+                transport,
+                typing: Cell::new(false),
+                last_sent: Cell::new(0.0),
+                stop_timer: Cell::new(None),
+            }),
+        }
+    }
+
+    // Llamar con cada cambio del input
+    pub fn on_input(&self, is_empty: bool) {
+        if is_empty {
+            self.stop();
+            return;
+        }
+
+        let now = js_sys::Date::now();
+        if !self.inner.typing.get() || now - self.inner.last_sent.get() >= TYPING_REFRESH_MS {
+            self.inner.typing.set(true);
+            self.inner.last_sent.set(now);
+            self.inner.transport.send_typing(true);
+        }
+
+        clear_timer(&self.inner.stop_timer);
+        let inner_ref = Rc::downgrade(&self.inner);
+        let stop_callback = Closure::once_into_js(move || {
+            let Some(inner) = inner_ref.upgrade() else { return };
+            inner.stop_timer.set(None);
+            Self { inner }.stop();
+        });
+        self.inner.stop_timer.set(set_timer(&stop_callback, TYPING_STOP_DELAY_MS));
+    }
+
+    pub fn stop(&self) {
+        clear_timer(&self.inner.stop_timer);
+        if self.inner.typing.replace(false) {
+            self.inner.transport.send_typing(false);
+        }
+    }
+}
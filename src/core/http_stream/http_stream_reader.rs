@@ -0,0 +1,316 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{ReadableStreamDefaultReader, Response};
+use serde_json::Value;
+use crate::core::websocket::websocket_manager::WebSocketMessage;
+
+// Formatos de cuerpo en streaming que entiende el widget
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamFormat {
+    Ndjson, // Un objeto JSON por línea
+    Sse,    // text/event-stream leído con fetch
+}
+
+impl StreamFormat {
+    // Formato según el Content-Type; `None` si la respuesta no viene en streaming
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match mime.as_str() {
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" => Some(StreamFormat::Ndjson),
+            "text/event-stream" => Some(StreamFormat::Sse),
+            _ => None,
+        }
+    }
+}
+
+// Lo que aporta cada línea (NDJSON) o evento (SSE) a la respuesta
+#[derive(Debug, PartialEq)]
+enum StreamEvent {
+    Delta(String),
+    Done,
+    Failed(String),
+    Ignore,
+}
+
+// Corta los bytes en líneas completas. Un salto de línea nunca forma parte de
+// un carácter UTF-8 multibyte, así que se puede cortar ahí sin decodificar antes.
+#[derive(Debug, Default)]
+struct LineDecoder {
+    buffer: Vec<u8>,
+}
+
+impl LineDecoder {
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut lines = Vec::new();
+        while let Some(position) = self.buffer.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=position).collect();
+            let line = String::from_utf8_lossy(&line);
+            lines.push(line.trim_end_matches(['\n', '\r']).to_string());
+        }
+        lines
+    }
+
+    // Lo que quedó sin salto de línea al terminar el cuerpo
+    fn finish(&mut self) -> Option<String> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        let line = String::from_utf8_lossy(&self.buffer).trim_end_matches('\r').to_string();
+        self.buffer.clear();
+        Some(line)
+    }
+}
+
+// Junta las líneas `data:` de un evento SSE hasta la línea en blanco que lo cierra
+#[derive(Debug, Default)]
+struct SseEventBuilder {
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseEventBuilder {
+    fn push_line(&mut self, line: &str) -> Option<StreamEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        // Comentarios, usados como keep-alive
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            _ => {} // id y retry solo importan con EventSource
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<StreamEvent> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        let data = self.data.drain(..).collect::<Vec<_>>().join("\n");
+
+        Some(match event.as_deref() {
+            Some("done") | Some("end") => StreamEvent::Done,
+            Some("error") => StreamEvent::Failed(data),
+            _ => parse_payload(&data, true),
+        })
+    }
+}
+
+// Interpretar el contenido de una línea o evento. Se aceptan los mismos frames
+// que por WebSocket (`message_delta`, `message_end`), `{ "delta": "..." }`,
+// `{ "done": true }`, el centinela `[DONE]` y, en SSE, texto plano.
+fn parse_payload(payload: &str, plain_text_allowed: bool) -> StreamEvent {
+    let payload = payload.trim();
+    if payload.is_empty() {
+        return StreamEvent::Ignore;
+    }
+    if payload == "[DONE]" {
+        return StreamEvent::Done;
+    }
+
+    let Ok(value) = serde_json::from_str::<Value>(payload) else {
+        if plain_text_allowed {
+            return StreamEvent::Delta(payload.to_string());
+        }
+        web_sys::console::log_1(&format!("Ignoring invalid stream line: {}", payload).into());
+        return StreamEvent::Ignore;
+    };
+
+    if value.get("type").is_some() {
+        return match serde_json::from_value::<WebSocketMessage>(value) {
+            Ok(WebSocketMessage::MessageDelta { delta, .. }) => StreamEvent::Delta(delta),
+            Ok(WebSocketMessage::MessageEnd { .. }) => StreamEvent::Done,
+            Ok(WebSocketMessage::Error { message, .. }) => StreamEvent::Failed(message),
+            _ => StreamEvent::Ignore,
+        };
+    }
+
+    if let Some(error) = value.get("error").and_then(Value::as_str) {
+        return StreamEvent::Failed(error.to_string());
+    }
+    if let Some(delta) = value.get("delta").and_then(Value::as_str) {
+        return StreamEvent::Delta(delta.to_string());
+    }
+    if value.get("done").and_then(Value::as_bool) == Some(true) {
+        return StreamEvent::Done;
+    }
+    StreamEvent::Ignore
+}
+
+// Leer el cuerpo de la respuesta a medida que llega, pasando cada parte del
+// texto a `on_delta`. Devuelve la respuesta completa.
+pub async fn read_stream<F>(response: &Response, format: StreamFormat, mut on_delta: F) -> Result<String, JsValue>
+where
+    F: FnMut(&str),
+{
+    let body = response.body().ok_or_else(|| JsValue::from_str("Response has no body"))?;
+    let reader: ReadableStreamDefaultReader = body.get_reader().unchecked_into();
+
+    let mut lines = LineDecoder::default();
+    let mut sse = SseEventBuilder::default();
+    let mut text = String::new();
+
+    let mut handle_line = |line: &str, text: &mut String| -> Result<bool, JsValue> {
+        let event = match format {
+            StreamFormat::Ndjson => Some(parse_payload(line, false)),
+            StreamFormat::Sse => sse.push_line(line),
+        };
+
+        match event {
+            Some(StreamEvent::Delta(delta)) => {
+                text.push_str(&delta);
+                on_delta(&delta);
+                Ok(false)
+            }
+            Some(StreamEvent::Done) => Ok(true),
+            Some(StreamEvent::Failed(error)) => Err(JsValue::from_str(&error)),
+            Some(StreamEvent::Ignore) | None => Ok(false),
+        }
+    };
+
+    let mut finished = false;
+    'read: loop {
+        let chunk = JsFuture::from(reader.read()).await?;
+        let done = js_sys::Reflect::get(&chunk, &JsValue::from_str("done"))?.as_bool().unwrap_or(true);
+        if done {
+            break;
+        }
+
+        let value = js_sys::Reflect::get(&chunk, &JsValue::from_str("value"))?;
+        let bytes = js_sys::Uint8Array::new(&value).to_vec();
+        for line in lines.push(&bytes) {
+            if handle_line(&line, &mut text)? {
+                // El servidor marcó el final: no esperar a que cierre la conexión
+                let _ = reader.cancel();
+                finished = true;
+                break 'read;
+            }
+        }
+    }
+
+    // Última línea sin salto y, en SSE, el evento que no llegó a cerrarse
+    if !finished {
+        if let Some(line) = lines.finish() {
+            finished = handle_line(&line, &mut text)?;
+        }
+        if !finished && format == StreamFormat::Sse {
+            handle_line("", &mut text)?;
+        }
+    }
+
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pasar los trozos al decodificador y juntar las líneas que salen
+    fn decode(chunks: &[&[u8]]) -> (Vec<String>, Option<String>) {
+        let mut decoder = LineDecoder::default();
+        let lines = chunks.iter().flat_map(|chunk| decoder.push(chunk)).collect();
+        (lines, decoder.finish())
+    }
+
+    // Eventos SSE que salen de un cuerpo ya cortado en líneas
+    fn sse_events(body: &str) -> Vec<StreamEvent> {
+        let mut builder = SseEventBuilder::default();
+        let (lines, rest) = decode(&[body.as_bytes()]);
+        lines.iter().chain(rest.iter()).filter_map(|line| builder.push_line(line)).collect()
+    }
+
+    #[test]
+    fn detects_the_format_from_the_content_type() {
+        assert_eq!(StreamFormat::from_content_type("application/x-ndjson"), Some(StreamFormat::Ndjson));
+        assert_eq!(StreamFormat::from_content_type("Text/Event-Stream; charset=utf-8"), Some(StreamFormat::Sse));
+        assert_eq!(StreamFormat::from_content_type("application/json"), None);
+    }
+
+    #[test]
+    fn joins_a_line_split_across_chunks() {
+        let (lines, rest) = decode(&[b"{\"delta\":\"Ho", b"la\"}\n{\"done\"", b":true}\n"]);
+        assert_eq!(lines, vec!["{\"delta\":\"Hola\"}", "{\"done\":true}"]);
+        assert_eq!(rest, None);
+    }
+
+    #[test]
+    fn strips_crlf_even_when_split_between_chunks() {
+        let (lines, _) = decode(&[b"data: uno\r", b"\n\r\n"]);
+        assert_eq!(lines, vec!["data: uno", ""]);
+    }
+
+    #[test]
+    fn keeps_multibyte_characters_split_across_chunks() {
+        let bytes = "{\"delta\":\"¿Qué?\"}\n".as_bytes();
+        // Cortar en medio de la "¿" (dos bytes en UTF-8)
+        let (lines, _) = decode(&[&bytes[..11], &bytes[11..]]);
+        assert_eq!(lines, vec!["{\"delta\":\"¿Qué?\"}"]);
+    }
+
+    #[test]
+    fn returns_the_trailing_line_without_newline_on_finish() {
+        let (lines, rest) = decode(&[b"{\"delta\":\"a\"}\n{\"delta\"", b":\"b\"}"]);
+        assert_eq!(lines, vec!["{\"delta\":\"a\"}"]);
+        assert_eq!(rest.as_deref(), Some("{\"delta\":\"b\"}"));
+    }
+
+    #[test]
+    fn parses_ndjson_payloads() {
+        assert_eq!(parse_payload("{\"delta\":\"Hola\"}", false), StreamEvent::Delta("Hola".to_string()));
+        assert_eq!(parse_payload("{\"done\":true}", false), StreamEvent::Done);
+        assert_eq!(parse_payload("[DONE]", false), StreamEvent::Done);
+        assert_eq!(parse_payload("{\"error\":\"boom\"}", false), StreamEvent::Failed("boom".to_string()));
+        assert_eq!(parse_payload("   ", false), StreamEvent::Ignore);
+    }
+
+    #[test]
+    fn parses_websocket_frames_in_the_stream() {
+        let delta = "{\"v\":2,\"type\":\"message_delta\",\"message_id\":\"m1\",\"delta\":\"Hola\"}";
+        let end = "{\"v\":2,\"type\":\"message_end\",\"message_id\":\"m1\"}";
+        assert_eq!(parse_payload(delta, false), StreamEvent::Delta("Hola".to_string()));
+        assert_eq!(parse_payload(end, false), StreamEvent::Done);
+    }
+
+    #[test]
+    fn builds_sse_events_from_data_lines() {
+        let events = sse_events(": keep-alive\ndata: Ho\n\ndata: {\"delta\":\"la\"}\n\nevent: done\ndata: x\n\n");
+        assert_eq!(events, vec![
+            StreamEvent::Delta("Ho".to_string()),
+            StreamEvent::Delta("la".to_string()),
+            StreamEvent::Done,
+        ]);
+    }
+
+    #[test]
+    fn joins_multiline_sse_data_and_reports_errors() {
+        let events = sse_events("data: uno\ndata: dos\n\nevent: error\ndata: boom\n\n");
+        assert_eq!(events, vec![
+            StreamEvent::Delta("uno\ndos".to_string()),
+            StreamEvent::Failed("boom".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn sse_event_split_across_chunks_is_delivered_once() {
+        let mut decoder = LineDecoder::default();
+        let mut builder = SseEventBuilder::default();
+        let mut events = Vec::new();
+        for chunk in [&b"da"[..], b"ta: Hola\n", b"\n"] {
+            for line in decoder.push(chunk) {
+                events.extend(builder.push_line(&line));
+            }
+        }
+        assert_eq!(events, vec![StreamEvent::Delta("Hola".to_string())]);
+    }
+}
@@ -0,0 +1 @@
+pub mod http_stream_reader;
\ No newline at end of file
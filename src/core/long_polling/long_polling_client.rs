@@ -0,0 +1,363 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{AbortController, Request, RequestInit, RequestMode, Response};
+use serde::Deserialize;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::{Rc, Weak};
+use crate::core::websocket::websocket_manager::{cancel_timer, set_timer, ConnectionState, WebSocketMessage};
+use crate::core::websocket::wire_format;
+use crate::core::transport::backoff::backoff_delay;
+use crate::core::transport::chat_transport::{ChatReply, ChatTransport, Receipt, TransportEvent, TransportFuture, TransportListener, TransportListeners};
+use crate::core::transport::connection_waiters::ConnectionWaiters;
+use crate::core::transport::http_request::{post_frame, url_with_params};
+use crate::core::transport::reply_tracker::ReplyTracker;
+
+// Espera máxima que se pide al servidor antes de responder sin mensajes
+const POLL_TIMEOUT_SECS: u32 = 25;
+
+// Margen sobre el timeout del servidor antes de dar la petición por colgada
+const POLL_GRACE_MS: u32 = 10_000;
+
+// Tiempo máximo de espera para la primera respuesta
+const CONNECT_TIMEOUT_MS: u32 = 10_000;
+
+// Tiempo máximo sin noticias de la respuesta del servidor
+const REPLY_TIMEOUT_MS: u32 = 30_000;
+
+// Espera entre reintentos tras un fallo, con backoff exponencial
+const RETRY_INTERVAL_MS: u32 = 1_000;
+
+// Respuesta de cada petición: los frames posteriores al cursor enviado y el
+// cursor desde el que pedir los siguientes
+#[derive(Debug, Deserialize)]
+struct PollResponse {
+    #[serde(default)]
+    cursor: Option<String>,
+    #[serde(default)]
+    messages: Vec<serde_json::Value>,
+}
+
+struct LongPollingInner {
+    url: String,
+    api_key: String,
+    user_id: String,
+    // Room al que van los mensajes; el servidor lo lee de cada ChatMessage
+    room: RefCell<Option<String>>,
+
+    // Posición en el stream del servidor; se conserva entre reconexiones
+    cursor: RefCell<Option<String>>,
+    state: RefCell<ConnectionState>,
+    // Cambia al desconectar para que el bucle en curso termine
+    generation: Cell<u32>,
+    polling: Cell<bool>,
+    abort_controller: RefCell<Option<AbortController>>,
+
+    connection_waiters: ConnectionWaiters,
+    replies: ReplyTracker,
+    listeners: TransportListeners,
+}
+
+// Cliente de chat por long-polling: recibe el mismo stream de `WebSocketMessage`
+// con peticiones HTTP repetidas y envía los mensajes con POST a la misma URL.
+// Último recurso para redes que bloquean WebSocket y SSE.
+#[derive(Clone)]
+pub struct LongPollingClient {
+    inner: Rc<LongPollingInner>,
+}
+
+impl LongPollingInner {
+    fn set_state(&self, state: ConnectionState) {
+        *self.state.borrow_mut() = state.clone();
+        self.listeners.emit(TransportEvent::StateChanged(state));
+    }
+
+    // Frames del servidor que no son la respuesta a un mensaje enviado
+    fn emit_message(&self, message: WebSocketMessage) {
+        self.listeners.emit(TransportEvent::Message(message));
+    }
+
+    fn abort_poll(&self) {
+        if let Some(controller) = self.abort_controller.borrow_mut().take() {
+            controller.abort();
+        }
+    }
+
+    fn poll_url(&self) -> String {
+        let timeout = POLL_TIMEOUT_SECS.to_string();
+        let cursor = self.cursor.borrow().clone();
+
+        let mut params = vec![
+            ("user_id", self.user_id.as_str()),
+            ("apiKey", self.api_key.as_str()),
+            ("timeout", timeout.as_str()),
+        ];
+        if let Some(cursor) = &cursor {
+            params.push(("cursor", cursor.as_str()));
+        }
+        url_with_params(&self.url, &params)
+    }
+
+    fn handle_response(&self, response: PollResponse) {
+        if response.cursor.is_some() {
+            *self.cursor.borrow_mut() = response.cursor;
+        }
+
+        for value in response.messages {
+            match wire_format::decode(&value.to_string()) {
+                Ok(frame) => {
+                    if !self.replies.handle(&frame.message) {
+                        self.emit_message(frame.message);
+                    }
+                }
+                Err(error_msg) => web_sys::console::log_1(&error_msg.into()),
+            }
+        }
+    }
+}
+
+impl LongPollingClient {
+    pub fn new(url: String, api_key: String, user_id: String) -> Self {
+        Self {
+            inner: Rc::new(LongPollingInner {
+                url,
+                api_key,
+                user_id,
+                room: RefCell::new(None),
+                cursor: RefCell::new(None),
+                state: RefCell::new(ConnectionState::Disconnected),
+                generation: Cell::new(0),
+                polling: Cell::new(false),
+                abort_controller: RefCell::new(None),
+                connection_waiters: ConnectionWaiters::default(),
+                replies: ReplyTracker::new(REPLY_TIMEOUT_MS),
+                listeners: TransportListeners::default(),
+            }),
+        }
+    }
+
+    pub fn get_connection_state(&self) -> ConnectionState {
+        self.inner.state.borrow().clone()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        matches!(*self.inner.state.borrow(), ConnectionState::Connected)
+    }
+
+    // Empezar a sondear (si hace falta) y esperar la primera respuesta
+    pub async fn connect(&self) -> Result<(), JsValue> {
+        if self.is_connected() {
+            return Ok(());
+        }
+
+        let promise = self.inner.connection_waiters.wait(CONNECT_TIMEOUT_MS);
+        if !self.inner.polling.get() {
+            self.inner.polling.set(true);
+            self.inner.set_state(ConnectionState::Connecting);
+
+            let inner_ref = Rc::downgrade(&self.inner);
+            let generation = self.inner.generation.get();
+            wasm_bindgen_futures::spawn_local(Self::poll_loop(inner_ref, generation));
+        }
+
+        JsFuture::from(promise).await?;
+        Ok(())
+    }
+
+    pub fn disconnect(&self) {
+        self.inner.generation.set(self.inner.generation.get().wrapping_add(1));
+        self.inner.polling.set(false);
+        self.inner.abort_poll();
+        self.inner.replies.reject_all("Disconnected");
+        self.inner.connection_waiters.reject_all("Disconnected");
+        self.inner.set_state(ConnectionState::Disconnected);
+    }
+
+    // Enviar un frame sin respuesta esperada (escritura, acuses) sin bloquear
+    fn post_in_background(&self, message: WebSocketMessage) {
+        let url = url_with_params(&self.inner.url, &[("apiKey", self.inner.api_key.as_str())]);
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = post_frame(&url, &message).await {
+                web_sys::console::log_1(&format!("Long-polling frame failed: {:?}", e).into());
+            }
+        });
+    }
+
+    // Enviar un mensaje por POST y esperar la respuesta en los siguientes sondeos
+    pub async fn send_chat_message(&self, message_id: &str, content: &str) -> Result<ChatReply, JsValue> {
+        self.connect().await?;
+
+        let message = WebSocketMessage::ChatMessage {
+            id: message_id.to_string(),
+            content: content.to_string(),
+            user_id: self.inner.user_id.clone(),
+            timestamp: js_sys::Date::now() as u64,
+            room_id: self.inner.room.borrow().clone(),
+            reply_to: None,
+        };
+
+        // Registrar la espera antes del POST para no perder una respuesta rápida
+        let promise = self.inner.replies.wait(message_id);
+        let url = url_with_params(&self.inner.url, &[("apiKey", self.inner.api_key.as_str())]);
+        if let Err(e) = post_frame(&url, &message).await {
+            let reason = e.as_string().unwrap_or_else(|| "Failed to send message".to_string());
+            self.inner.replies.reject(message_id, &reason);
+        }
+
+        ReplyTracker::reply(promise).await
+    }
+
+    // Sondear mientras no se desconecte. Solo se mantiene una referencia débil
+    // durante las esperas para que soltar el cliente detenga el bucle.
+    async fn poll_loop(inner_ref: Weak<LongPollingInner>, generation: u32) {
+        let mut failures: u32 = 0;
+
+        loop {
+            let (url, controller) = {
+                let Some(inner) = inner_ref.upgrade() else { return };
+                if inner.generation.get() != generation {
+                    return;
+                }
+
+                let controller = AbortController::new().ok();
+                *inner.abort_controller.borrow_mut() = controller.clone();
+                (inner.poll_url(), controller)
+            };
+
+            let result = Self::poll_once(&url, controller.as_ref()).await;
+
+            let Some(inner) = inner_ref.upgrade() else { return };
+            if inner.generation.get() != generation {
+                return;
+            }
+
+            match result {
+                Ok(response) => {
+                    failures = 0;
+                    if !matches!(*inner.state.borrow(), ConnectionState::Connected) {
+                        inner.set_state(ConnectionState::Connected);
+                        inner.connection_waiters.resolve_all();
+                    }
+                    if let Some(response) = response {
+                        inner.handle_response(response);
+                    }
+                }
+                Err(e) => {
+                    web_sys::console::log_1(&format!("Long-polling request failed: {:?}", e).into());
+                    failures += 1;
+                    inner.set_state(ConnectionState::Reconnecting);
+                    inner.connection_waiters.reject_all("Long-polling connection failed");
+                    drop(inner);
+
+                    // Backoff exponencial con jitter, como el WebSocket
+                    sleep(backoff_delay(RETRY_INTERVAL_MS, failures - 1)).await;
+                }
+            }
+        }
+    }
+
+    // Una petición de sondeo. `None` si el servidor respondió sin mensajes.
+    async fn poll_once(url: &str, controller: Option<&AbortController>) -> Result<Option<PollResponse>, JsValue> {
+        let opts = RequestInit::new();
+        opts.set_method("GET");
+        opts.set_mode(RequestMode::Cors);
+
+        // Si el servidor no cierra a tiempo la petición, abortarla y reintentar
+        let abort_timer = controller.and_then(|controller| {
+            opts.set_signal(Some(&controller.signal()));
+            let controller = controller.clone();
+            let abort_callback = Closure::once_into_js(move || controller.abort());
+            set_timer(&abort_callback, POLL_TIMEOUT_SECS * 1000 + POLL_GRACE_MS)
+        });
+
+        let request = Request::new_with_str_and_init(url, &opts)
+            .map_err(|e| JsValue::from_str(&format!("Failed to create request: {:?}", e)))?;
+
+        let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window available"))?;
+        let result = JsFuture::from(window.fetch_with_request(&request)).await;
+        cancel_timer(abort_timer);
+
+        let resp: Response = result?.dyn_into()?;
+        match resp.status() {
+            204 => Ok(None),
+            200 => {
+                let text = JsFuture::from(resp.text()?).await?.as_string().unwrap_or_default();
+                serde_json::from_str(&text)
+                    .map(Some)
+                    .map_err(|e| JsValue::from_str(&format!("Failed to parse poll response: {}", e)))
+            }
+            status => Err(JsValue::from_str(&format!("Poll request failed (HTTP {})", status))),
+        }
+    }
+}
+
+async fn sleep(delay_ms: u32) {
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        set_timer(&resolve, delay_ms);
+    });
+    let _ = JsFuture::from(promise).await;
+}
+
+impl ChatTransport for LongPollingClient {
+    fn name(&self) -> &'static str {
+        "long-polling"
+    }
+
+    fn connect(&self) -> TransportFuture<'_, ()> {
+        Box::pin(LongPollingClient::connect(self))
+    }
+
+    // Las respuestas en streaming llegan como frames `message_delta`
+    fn send<'a>(&'a self, message_id: &'a str, content: &'a str, _on_delta: &'a mut dyn FnMut(&str)) -> TransportFuture<'a, ChatReply> {
+        Box::pin(self.send_chat_message(message_id, content))
+    }
+
+    fn send_typing(&self, is_typing: bool) {
+        if !self.is_connected() {
+            return;
+        }
+
+        self.post_in_background(WebSocketMessage::UserTyping {
+            user_id: self.inner.user_id.clone(),
+            is_typing,
+        });
+    }
+
+    fn send_receipt(&self, message_id: &str, receipt: Receipt) {
+        self.post_in_background(receipt.frame(message_id, &self.inner.user_id));
+    }
+
+    // Sin suscripción por room: basta con indicarlo en cada mensaje
+    fn set_room(&self, room: Option<String>) -> Result<(), JsValue> {
+        *self.inner.room.borrow_mut() = room;
+        Ok(())
+    }
+
+    fn subscribe(&self, listener: TransportListener) {
+        self.inner.listeners.add(listener);
+    }
+
+    fn close(&self) {
+        self.disconnect();
+    }
+
+    fn state(&self) -> ConnectionState {
+        self.get_connection_state()
+    }
+}
+
+impl fmt::Debug for LongPollingClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LongPollingClient")
+            .field("url", &self.inner.url)
+            .field("connection_state", &*self.inner.state.borrow())
+            .finish()
+    }
+}
+
+// Cortar el sondeo en curso cuando se suelta el último handle
+impl Drop for LongPollingInner {
+    fn drop(&mut self) {
+        self.abort_poll();
+    }
+}
@@ -0,0 +1 @@
+pub mod long_polling_client;
\ No newline at end of file
@@ -1,4 +1,8 @@
 pub mod grace_chat_element;
 pub mod api_key_validation;
 pub mod grace_chat_config;
-pub mod websocket;
\ No newline at end of file
+pub mod websocket;
+pub mod http_stream;
+pub mod sse;
+pub mod long_polling;
+pub mod transport;
\ No newline at end of file
@@ -0,0 +1 @@
+pub mod sse_chat_client;
\ No newline at end of file
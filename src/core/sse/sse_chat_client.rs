@@ -0,0 +1,342 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{EventSource, MessageEvent};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+use crate::core::websocket::websocket_manager::{clear_timer, set_timer, ConnectionState, WebSocketMessage};
+use crate::core::websocket::wire_format;
+use crate::core::transport::backoff::backoff_delay;
+use crate::core::transport::chat_transport::{ChatReply, ChatTransport, Receipt, TransportEvent, TransportFuture, TransportListener, TransportListeners};
+use crate::core::transport::connection_waiters::ConnectionWaiters;
+use crate::core::transport::http_request::{post_frame, url_with_params};
+use crate::core::transport::reply_tracker::ReplyTracker;
+
+// Tiempo máximo de espera para abrir el canal de eventos
+const CONNECT_TIMEOUT_MS: u32 = 10_000;
+
+// Tiempo máximo sin noticias de la respuesta del servidor
+const REPLY_TIMEOUT_MS: u32 = 30_000;
+
+// Reconexión propia cuando el navegador deja de reintentar
+const RECONNECT_INTERVAL_MS: u32 = 1_000;
+
+struct SseInner {
+    url: String,
+    api_key: String,
+    user_id: String,
+    // Room al que van los mensajes; el servidor lo lee de cada ChatMessage
+    room: RefCell<Option<String>>,
+
+    event_source: RefCell<Option<EventSource>>,
+    // Último id recibido, para retomar donde se quedó el stream
+    last_event_id: RefCell<Option<String>>,
+    state: RefCell<ConnectionState>,
+    reconnect_attempts: Cell<u32>,
+    reconnect_timer: Cell<Option<i32>>,
+
+    connection_waiters: ConnectionWaiters,
+    replies: ReplyTracker,
+    listeners: TransportListeners,
+}
+
+// Cliente de chat sobre Server-Sent Events: recibe los frames del servidor por
+// `EventSource` y envía los mensajes del usuario con HTTP POST a la misma URL.
+// Para proxies que cortan los upgrades a WebSocket pero dejan pasar SSE.
+#[derive(Clone)]
+pub struct SseChatClient {
+    inner: Rc<SseInner>,
+}
+
+impl SseInner {
+    fn set_state(&self, state: ConnectionState) {
+        *self.state.borrow_mut() = state.clone();
+        self.listeners.emit(TransportEvent::StateChanged(state));
+    }
+
+    // Frames del servidor que no son la respuesta a un mensaje enviado
+    fn emit_message(&self, message: WebSocketMessage) {
+        self.listeners.emit(TransportEvent::Message(message));
+    }
+
+    fn is_current(&self, event_source: &EventSource) -> bool {
+        self.event_source.borrow().as_ref() == Some(event_source)
+    }
+
+    // Soltar el EventSource actual sin que sus handlers sigan disparando
+    fn close_event_source(&self) {
+        if let Some(event_source) = self.event_source.borrow_mut().take() {
+            event_source.set_onopen(None);
+            event_source.set_onmessage(None);
+            event_source.set_onerror(None);
+            event_source.close();
+        }
+    }
+}
+
+impl SseChatClient {
+    pub fn new(url: String, api_key: String, user_id: String) -> Self {
+        Self {
+            inner: Rc::new(SseInner {
+                url,
+                api_key,
+                user_id,
+                room: RefCell::new(None),
+                event_source: RefCell::new(None),
+                last_event_id: RefCell::new(None),
+                state: RefCell::new(ConnectionState::Disconnected),
+                reconnect_attempts: Cell::new(0),
+                reconnect_timer: Cell::new(None),
+                connection_waiters: ConnectionWaiters::default(),
+                replies: ReplyTracker::new(REPLY_TIMEOUT_MS),
+                listeners: TransportListeners::default(),
+            }),
+        }
+    }
+
+    pub fn get_connection_state(&self) -> ConnectionState {
+        self.inner.state.borrow().clone()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        matches!(*self.inner.state.borrow(), ConnectionState::Connected)
+    }
+
+    // Abrir el canal de eventos (si hace falta) y esperar a que esté listo
+    pub async fn connect(&self) -> Result<(), JsValue> {
+        if self.is_connected() {
+            return Ok(());
+        }
+
+        let promise = self.inner.connection_waiters.wait(CONNECT_TIMEOUT_MS);
+        if self.inner.event_source.borrow().is_none() && self.inner.reconnect_timer.get().is_none() {
+            self.inner.set_state(ConnectionState::Connecting);
+            Self::open_event_source(&self.inner)?;
+        }
+
+        JsFuture::from(promise).await?;
+        Ok(())
+    }
+
+    pub fn disconnect(&self) {
+        clear_timer(&self.inner.reconnect_timer);
+        self.inner.close_event_source();
+        *self.inner.last_event_id.borrow_mut() = None;
+        self.inner.replies.reject_all("Disconnected");
+        self.inner.connection_waiters.reject_all("Disconnected");
+        self.inner.set_state(ConnectionState::Disconnected);
+    }
+
+    // Enviar un frame sin respuesta esperada (escritura, acuses) sin bloquear
+    fn post_in_background(&self, message: WebSocketMessage) {
+        let url = url_with_params(&self.inner.url, &[("apiKey", self.inner.api_key.as_str())]);
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = post_frame(&url, &message).await {
+                web_sys::console::log_1(&format!("SSE frame failed: {:?}", e).into());
+            }
+        });
+    }
+
+    // Enviar un mensaje por POST y esperar la respuesta que llega por SSE
+    pub async fn send_chat_message(&self, message_id: &str, content: &str) -> Result<ChatReply, JsValue> {
+        self.connect().await?;
+
+        let message = WebSocketMessage::ChatMessage {
+            id: message_id.to_string(),
+            content: content.to_string(),
+            user_id: self.inner.user_id.clone(),
+            timestamp: js_sys::Date::now() as u64,
+            room_id: self.inner.room.borrow().clone(),
+            reply_to: None,
+        };
+
+        // Registrar la espera antes del POST para no perder una respuesta rápida
+        let promise = self.inner.replies.wait(message_id);
+        let url = url_with_params(&self.inner.url, &[("apiKey", self.inner.api_key.as_str())]);
+        if let Err(e) = post_frame(&url, &message).await {
+            let reason = e.as_string().unwrap_or_else(|| "Failed to send message".to_string());
+            self.inner.replies.reject(message_id, &reason);
+        }
+
+        ReplyTracker::reply(promise).await
+    }
+
+    fn open_event_source(inner: &Rc<SseInner>) -> Result<(), JsValue> {
+        // EventSource no permite cabeceras: la autenticación va en la URL. Al
+        // reconectar por nuestra cuenta, el último id viaja como `lastEventId`;
+        // en los reintentos del navegador llega como cabecera `Last-Event-ID`.
+        let mut params = vec![("user_id", inner.user_id.as_str()), ("apiKey", inner.api_key.as_str())];
+        let last_event_id = inner.last_event_id.borrow().clone();
+        if let Some(last_event_id) = &last_event_id {
+            params.push(("lastEventId", last_event_id.as_str()));
+        }
+        let url = url_with_params(&inner.url, &params);
+
+        web_sys::console::log_1(&format!("Connecting to SSE: {}", inner.url).into());
+        let event_source = EventSource::new(&url)
+            .map_err(|e| JsValue::from_str(&format!("Failed to create EventSource: {:?}", e)))?;
+
+        Self::setup_event_listeners(inner, &event_source);
+        *inner.event_source.borrow_mut() = Some(event_source);
+        Ok(())
+    }
+
+    fn setup_event_listeners(inner: &Rc<SseInner>, event_source: &EventSource) {
+        // OnOpen
+        {
+            let inner_ref = Rc::downgrade(inner);
+
+            let onopen_callback = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+                web_sys::console::log_1(&"SSE connection opened".into());
+
+                let Some(inner) = inner_ref.upgrade() else { return };
+                inner.reconnect_attempts.set(0);
+                inner.set_state(ConnectionState::Connected);
+                inner.connection_waiters.resolve_all();
+            }) as Box<dyn FnMut(_)>);
+
+            event_source.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
+            onopen_callback.forget();
+        }
+
+        // OnMessage
+        {
+            let inner_ref = Rc::downgrade(inner);
+
+            let onmessage_callback = Closure::wrap(Box::new(move |event: MessageEvent| {
+                let Some(inner) = inner_ref.upgrade() else { return };
+
+                let last_event_id = event.last_event_id();
+                if !last_event_id.is_empty() {
+                    *inner.last_event_id.borrow_mut() = Some(last_event_id);
+                }
+
+                let data = event.data().as_string().unwrap_or_default();
+                match wire_format::decode(&data) {
+                    Ok(frame) => {
+                        if !inner.replies.handle(&frame.message) {
+                            inner.emit_message(frame.message);
+                        }
+                    }
+                    Err(error_msg) => web_sys::console::log_1(&error_msg.into()),
+                }
+            }) as Box<dyn FnMut(_)>);
+
+            event_source.set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
+            onmessage_callback.forget();
+        }
+
+        // OnError
+        {
+            let inner_ref = Rc::downgrade(inner);
+            let event_source_clone = event_source.clone();
+
+            let onerror_callback = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+                let Some(inner) = inner_ref.upgrade() else { return };
+                if !inner.is_current(&event_source_clone) {
+                    return;
+                }
+
+                if event_source_clone.ready_state() == EventSource::CONNECTING {
+                    // El navegador reintenta solo, enviando Last-Event-ID
+                    web_sys::console::log_1(&"SSE connection lost, browser is retrying".into());
+                    inner.set_state(ConnectionState::Reconnecting);
+                } else {
+                    // El navegador se rindió (por ejemplo, una respuesta HTTP de error)
+                    web_sys::console::log_1(&"SSE connection closed".into());
+                    inner.close_event_source();
+                    inner.connection_waiters.reject_all("SSE connection failed");
+                    Self::schedule_reconnect(&inner);
+                }
+            }) as Box<dyn FnMut(_)>);
+
+            event_source.set_onerror(Some(onerror_callback.as_ref().unchecked_ref()));
+            onerror_callback.forget();
+        }
+    }
+
+    fn schedule_reconnect(inner: &Rc<SseInner>) {
+        let attempt = inner.reconnect_attempts.get();
+        inner.reconnect_attempts.set(attempt + 1);
+        inner.set_state(ConnectionState::Reconnecting);
+
+        // Backoff exponencial con jitter, como el WebSocket
+        let delay = backoff_delay(RECONNECT_INTERVAL_MS, attempt);
+
+        let inner_ref = Rc::downgrade(inner);
+        let reconnect_callback = Closure::once_into_js(move || {
+            let Some(inner) = inner_ref.upgrade() else { return };
+            inner.reconnect_timer.set(None);
+            if let Err(e) = Self::open_event_source(&inner) {
+                web_sys::console::log_1(&format!("SSE reconnect failed: {:?}", e).into());
+                Self::schedule_reconnect(&inner);
+            }
+        });
+        inner.reconnect_timer.set(set_timer(&reconnect_callback, delay));
+    }
+}
+
+impl ChatTransport for SseChatClient {
+    fn name(&self) -> &'static str {
+        "sse"
+    }
+
+    fn connect(&self) -> TransportFuture<'_, ()> {
+        Box::pin(SseChatClient::connect(self))
+    }
+
+    // Las respuestas en streaming llegan como frames `message_delta`
+    fn send<'a>(&'a self, message_id: &'a str, content: &'a str, _on_delta: &'a mut dyn FnMut(&str)) -> TransportFuture<'a, ChatReply> {
+        Box::pin(self.send_chat_message(message_id, content))
+    }
+
+    fn send_typing(&self, is_typing: bool) {
+        if !self.is_connected() {
+            return;
+        }
+
+        self.post_in_background(WebSocketMessage::UserTyping {
+            user_id: self.inner.user_id.clone(),
+            is_typing,
+        });
+    }
+
+    fn send_receipt(&self, message_id: &str, receipt: Receipt) {
+        self.post_in_background(receipt.frame(message_id, &self.inner.user_id));
+    }
+
+    // Sin suscripción por room: basta con indicarlo en cada mensaje
+    fn set_room(&self, room: Option<String>) -> Result<(), JsValue> {
+        *self.inner.room.borrow_mut() = room;
+        Ok(())
+    }
+
+    fn subscribe(&self, listener: TransportListener) {
+        self.inner.listeners.add(listener);
+    }
+
+    fn close(&self) {
+        self.disconnect();
+    }
+
+    fn state(&self) -> ConnectionState {
+        self.get_connection_state()
+    }
+}
+
+impl fmt::Debug for SseChatClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SseChatClient")
+            .field("url", &self.inner.url)
+            .field("connection_state", &*self.inner.state.borrow())
+            .finish()
+    }
+}
+
+// Cerrar el canal cuando se suelta el último handle
+impl Drop for SseInner {
+    fn drop(&mut self) {
+        clear_timer(&self.reconnect_timer);
+        self.close_event_source();
+    }
+}
@@ -0,0 +1,34 @@
+// Límite superior del backoff exponencial entre reintentos
+pub const MAX_BACKOFF_DELAY_MS: u32 = 30_000;
+
+// Espera antes del reintento número `attempt` (desde 0): intervalo * 2^intento,
+// limitado y con jitter para que muchos clientes no reintenten al mismo tiempo.
+pub fn backoff_delay(base_interval: u32, attempt: u32) -> u32 {
+    let half = exponential_backoff(base_interval, attempt) / 2;
+    half + (js_sys::Math::random() * half as f64) as u32
+}
+
+fn exponential_backoff(base_interval: u32, attempt: u32) -> u32 {
+    base_interval
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF_DELAY_MS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_the_interval_on_each_attempt() {
+        assert_eq!(exponential_backoff(1_000, 0), 1_000);
+        assert_eq!(exponential_backoff(1_000, 1), 2_000);
+        assert_eq!(exponential_backoff(1_000, 4), 16_000);
+    }
+
+    #[test]
+    fn caps_the_delay_without_overflowing() {
+        assert_eq!(exponential_backoff(1_000, 5), MAX_BACKOFF_DELAY_MS);
+        assert_eq!(exponential_backoff(1_000, 40), MAX_BACKOFF_DELAY_MS);
+        assert_eq!(exponential_backoff(u32::MAX, 3), MAX_BACKOFF_DELAY_MS);
+    }
+}
@@ -0,0 +1,129 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use crate::core::websocket::websocket_manager::{ConnectionState, DeliveryStatus, WebSocketMessage};
+
+// Operación asíncrona de un transporte. Sin `Send`: todo corre en el hilo del navegador.
+pub type TransportFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, JsValue>> + 'a>>;
+
+// Lo que un transporte notifica a sus suscriptores
+#[derive(Debug, Clone)]
+pub enum TransportEvent {
+    // Frame del servidor que no es la respuesta a un mensaje enviado
+    Message(WebSocketMessage),
+    // Progreso de la entrega de un mensaje enviado
+    DeliveryStatus { message_id: String, status: DeliveryStatus },
+    StateChanged(ConnectionState),
+    // El transporte en uso cambió (modo híbrido); lleva su `name()`
+    TransportChanged(&'static str),
+}
+
+pub type TransportListener = Box<dyn FnMut(TransportEvent)>;
+
+// Respuesta del servidor a un mensaje enviado
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatReply {
+    // Id propio de la respuesta, para acusarla. HTTP y los `ServerResponse` no lo traen.
+    pub message_id: Option<String>,
+    pub text: String,
+}
+
+// Acuse que el widget envía por un mensaje del bot o de un agente
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Receipt {
+    Delivered, // Llegó al widget
+    Read,      // Se mostró en pantalla con el widget abierto
+}
+
+impl Receipt {
+    pub fn frame(self, message_id: &str, user_id: &str) -> WebSocketMessage {
+        let message_id = message_id.to_string();
+        let user_id = user_id.to_string();
+        let timestamp = js_sys::Date::now() as u64;
+        match self {
+            Receipt::Delivered => WebSocketMessage::MessageDelivered { message_id, user_id, timestamp },
+            Receipt::Read => WebSocketMessage::MessageRead { message_id, user_id, timestamp },
+        }
+    }
+}
+
+// Forma común de hablar con el servidor, sea por WebSocket, HTTP, SSE o
+// long-polling. El widget solo conoce este trait.
+pub trait ChatTransport: fmt::Debug {
+    // Nombre corto para logs y para la interfaz
+    fn name(&self) -> &'static str;
+
+    // Abrir el canal (si hace falta) y esperar a que esté listo
+    fn connect(&self) -> TransportFuture<'_, ()>;
+
+    // Enviar un mensaje y esperar la respuesta completa. Las respuestas HTTP en
+    // streaming pasan cada parte a `on_delta`; los transportes con canal de
+    // eventos las notifican como frames `message_delta`.
+    fn send<'a>(&'a self, message_id: &'a str, content: &'a str, on_delta: &'a mut dyn FnMut(&str)) -> TransportFuture<'a, ChatReply>;
+
+    // Avisar de que el usuario empezó o dejó de escribir. Es un aviso sin
+    // garantías: sin conexión (o sin canal de eventos, como HTTP) se descarta.
+    fn send_typing(&self, _is_typing: bool) {}
+
+    // Acusar recibo o lectura de un mensaje recibido. Sin canal de eventos se descarta.
+    fn send_receipt(&self, _message_id: &str, _receipt: Receipt) {}
+
+    // Cambiar de room: los mensajes siguientes van a esa conversación. Los
+    // transportes sin rooms (HTTP) lo ignoran.
+    fn set_room(&self, _room: Option<String>) -> Result<(), JsValue> {
+        Ok(())
+    }
+
+    // Editar o retirar un mensaje ya enviado. Solo los transportes que lo
+    // admiten (WebSocket) lo implementan; el resto devuelve un error.
+    fn edit_message(&self, _message_id: &str, _content: &str) -> Result<(), JsValue> {
+        Err(JsValue::from_str(&format!("{} does not support editing messages", self.name())))
+    }
+
+    fn delete_message(&self, _message_id: &str) -> Result<(), JsValue> {
+        Err(JsValue::from_str(&format!("{} does not support deleting messages", self.name())))
+    }
+
+    // Recibir los eventos del transporte
+    fn subscribe(&self, listener: TransportListener);
+
+    fn close(&self);
+
+    fn state(&self) -> ConnectionState;
+}
+
+// Suscriptores de un transporte. Los clones comparten la lista.
+#[derive(Clone, Default)]
+pub struct TransportListeners {
+    listeners: Rc<RefCell<Vec<TransportListener>>>,
+}
+
+impl TransportListeners {
+    pub fn add(&self, listener: TransportListener) {
+        self.listeners.borrow_mut().push(listener);
+    }
+
+    // Los listeners se sacan mientras se llaman para que puedan suscribir otros
+    pub fn emit(&self, event: TransportEvent) {
+        let mut listeners = std::mem::take(&mut *self.listeners.borrow_mut());
+        for listener in listeners.iter_mut() {
+            listener(event.clone());
+        }
+
+        let mut slot = self.listeners.borrow_mut();
+        listeners.append(&mut slot);
+        *slot = listeners;
+    }
+}
+
+impl fmt::Debug for TransportListeners {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransportListeners")
+            .field("count", &self.listeners.borrow().len())
+            .finish()
+    }
+}
@@ -0,0 +1,68 @@
+use wasm_bindgen::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use crate::core::websocket::websocket_manager::set_timer;
+
+// Promesa esperando a que se abra la conexión
+#[derive(Debug)]
+struct PendingWaiter {
+    id: u32,
+    resolve: js_sys::Function,
+    reject: js_sys::Function,
+}
+
+// Quienes esperan a que un transporte esté listo. Los clones comparten la cola.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionWaiters {
+    queue: Rc<RefCell<VecDeque<PendingWaiter>>>,
+    next_id: Rc<Cell<u32>>,
+}
+
+impl ConnectionWaiters {
+    // Registrar una promesa que se resuelve al conectar o se rechaza al vencer el timeout
+    pub fn wait(&self, timeout_ms: u32) -> js_sys::Promise {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1));
+
+        let mut callbacks = None;
+        let promise = js_sys::Promise::new(&mut |resolve, reject| {
+            callbacks = Some((resolve, reject));
+        });
+
+        if let Some((resolve, reject)) = callbacks {
+            self.queue.borrow_mut().push_back(PendingWaiter { id, resolve, reject });
+        }
+
+        let queue_ref = Rc::downgrade(&self.queue);
+        let timeout_callback = Closure::once_into_js(move || {
+            let Some(queue) = queue_ref.upgrade() else { return };
+            let waiter = {
+                let mut queue = queue.borrow_mut();
+                queue.iter()
+                    .position(|waiter| waiter.id == id)
+                    .and_then(|position| queue.remove(position))
+            };
+            if let Some(waiter) = waiter {
+                let _ = waiter.reject.call1(&JsValue::NULL, &JsValue::from_str("Connection timed out"));
+            }
+        });
+        set_timer(&timeout_callback, timeout_ms);
+
+        promise
+    }
+
+    pub fn resolve_all(&self) {
+        let waiters: Vec<_> = self.queue.borrow_mut().drain(..).collect();
+        for waiter in waiters {
+            let _ = waiter.resolve.call0(&JsValue::NULL);
+        }
+    }
+
+    pub fn reject_all(&self, reason: &str) {
+        let waiters: Vec<_> = self.queue.borrow_mut().drain(..).collect();
+        for waiter in waiters {
+            let _ = waiter.reject.call1(&JsValue::NULL, &JsValue::from_str(reason));
+        }
+    }
+}
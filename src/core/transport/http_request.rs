@@ -0,0 +1,139 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Headers, Request, RequestInit, RequestMode, Response};
+use crate::core::websocket::websocket_manager::WebSocketMessage;
+use crate::core::websocket::wire_format;
+
+// Enviar un frame por HTTP POST; la respuesta llega por el canal de eventos
+pub async fn post_frame(url: &str, message: &WebSocketMessage) -> Result<(), JsValue> {
+    let body = wire_format::encode(message).map_err(|e| JsValue::from_str(&e))?;
+
+    let opts = RequestInit::new();
+    opts.set_method("POST");
+    opts.set_mode(RequestMode::Cors);
+    opts.set_body(&JsValue::from_str(&body));
+
+    let headers = Headers::new()?;
+    headers.set("Content-Type", "application/json")?;
+    opts.set_headers(&headers);
+
+    let request = Request::new_with_str_and_init(url, &opts)
+        .map_err(|e| JsValue::from_str(&format!("Failed to create request: {:?}", e)))?;
+
+    let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window available"))?;
+    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
+    let resp: Response = resp_value.dyn_into()?;
+
+    if resp.ok() {
+        Ok(())
+    } else {
+        Err(JsValue::from_str(&format!("Failed to send message (HTTP {})", resp.status())))
+    }
+}
+
+// Añadir parámetros (codificados) a la query de una URL
+pub fn url_with_params(url: &str, params: &[(&str, &str)]) -> String {
+    let query: Vec<String> = params.iter()
+        .map(|(key, value)| {
+            let value = js_sys::encode_uri_component(value).as_string().unwrap_or_default();
+            format!("{}={}", key, value)
+        })
+        .collect();
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{}{}{}", url, separator, query.join("&"))
+}
+
+// Estructura para manejar respuestas HTTP de forma centralizada
+pub struct HttpHandler;
+
+impl HttpHandler {
+    // Hacer una petición HTTP GET
+    pub async fn get_request(url: &str) -> Result<Response, JsValue> {
+        let opts = RequestInit::new();
+        opts.set_method("GET");
+        opts.set_mode(RequestMode::Cors);
+
+        let request = Request::new_with_str_and_init(url, &opts)
+            .map_err(|e| JsValue::from_str(&format!("Failed to create request: {:?}", e)))?;
+
+        let window = web_sys::window().unwrap();
+        let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
+        let resp: Response = resp_value.dyn_into().unwrap();
+        
+        Ok(resp)
+    }
+
+    // Hacer una petición HTTP GET aceptando una respuesta en streaming
+    pub async fn get_stream_request(url: &str) -> Result<Response, JsValue> {
+        let opts = RequestInit::new();
+        opts.set_method("GET");
+        opts.set_mode(RequestMode::Cors);
+
+        let headers = Headers::new()?;
+        headers.set("Accept", "application/x-ndjson, text/event-stream, application/json")?;
+        opts.set_headers(&headers);
+
+        let request = Request::new_with_str_and_init(url, &opts)
+            .map_err(|e| JsValue::from_str(&format!("Failed to create request: {:?}", e)))?;
+
+        let window = web_sys::window().unwrap();
+        let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
+        let resp: Response = resp_value.dyn_into().unwrap();
+        
+        Ok(resp)
+    }
+
+    // Manejar respuestas de validación de API Key
+    pub fn handle_validation_response(status: u16, error_text: Option<String>) -> (bool, String) {
+        match status {
+            200 => (true, "API Key validation: SUCCESS".to_string()),
+            400 => (false, "API Key validation: FAILED - Bad Request".to_string()),
+            401 => {
+                let message = if let Some(text) = error_text {
+                    if text.contains("apiKeyInvalid") {
+                        "API Key validation: FAILED - Invalid API Key"
+                    } else if text.contains("apiKeyMissing") {
+                        "API Key validation: FAILED - Missing API Key"
+                    } else if text.contains("apiKeyDisabled") {
+                        "API Key validation: FAILED - API Key Disabled"
+                    } else if text.contains("apiKeyExhausted") {
+                        "API Key validation: FAILED - API Key Exhausted"
+                    } else {
+                        "API Key validation: FAILED - Unauthorized"
+                    }
+                } else {
+                    "API Key validation: FAILED - Unauthorized"
+                };
+                (false, message.to_string())
+            },
+            429 => (false, "API Key validation: FAILED - Rate Limited".to_string()),
+            500 => (false, "API Key validation: FAILED - Server Error".to_string()),
+            _ => (false, format!("API Key validation: FAILED - Unexpected status: {}", status)),
+        }
+    }
+
+    // Manejar respuestas de chat (para el usuario) - Unificado con validación
+    pub fn handle_chat_response(status: u16, error_text: Option<String>) -> String {
+        match status {
+            200 => "Success".to_string(), // Este caso se maneja diferente en chat
+            400 => "Lo siento, tu pregunta no es válida. ¿Podrías reformularla?".to_string(),
+            401 => {
+                // Podríamos hacer análisis similar al de validación si fuera necesario
+                if let Some(text) = error_text {
+                    if text.contains("apiKeyInvalid") || text.contains("apiKeyMissing") {
+                        "Tu API key no es válida. Por favor, contacta al administrador.".to_string()
+                    } else if text.contains("apiKeyDisabled") || text.contains("apiKeyExhausted") {
+                        "Tu API key ha sido deshabilitada o agotada. Contacta al administrador.".to_string()
+                    } else {
+                        "Hay un problema con la configuración del chat. Por favor, contacta al administrador.".to_string()
+                    }
+                } else {
+                    "Hay un problema con la configuración del chat. Por favor, contacta al administrador.".to_string()
+                }
+            },
+            429 => "Demasiadas consultas en este momento. Por favor, espera un momento e intenta de nuevo.".to_string(),
+            500 => "El servicio no está disponible en este momento. Por favor, intenta más tarde.".to_string(),
+            _ => format!("Error inesperado (código {}). Por favor, intenta de nuevo.", status),
+        }
+    }
+}
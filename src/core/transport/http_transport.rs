@@ -0,0 +1,189 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use serde::Deserialize;
+use crate::core::http_stream::http_stream_reader::{read_stream, StreamFormat};
+use crate::core::transport::chat_transport::{ChatReply, ChatTransport, TransportFuture, TransportListener};
+use crate::core::transport::http_request::HttpHandler;
+use crate::core::websocket::websocket_manager::ConnectionState;
+
+// Constante para el endpoint de chat HTTP
+pub const CHAT_ENDPOINT: &str = "https://newsapi.org/v2/everything";
+
+#[derive(Deserialize)]
+struct NewsApiResponse {
+    status: String,
+    #[serde(rename = "totalResults")]
+    total_results: Option<i32>,
+    articles: Option<Vec<Article>>,
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Article {
+    title: String,
+    description: Option<String>,
+    url: String,
+}
+
+// Transporte HTTP: una petición por mensaje, sin conexión que mantener.
+// Con `stream_url` la respuesta se lee a medida que llega.
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    api_key: String,
+    stream_url: Option<String>,
+}
+
+impl HttpTransport {
+    pub fn new(api_key: String, stream_url: Option<String>) -> Self {
+        Self { api_key, stream_url }
+    }
+
+    // Procesar mensaje via HTTP (método original)
+    async fn process_http_message<F>(&self, user_message: &str, on_delta: F) -> Result<String, JsValue>
+    where
+        F: FnMut(&str),
+    {
+        // Validar que tenemos API key
+        if self.api_key.is_empty() {
+            return Err(JsValue::from_str("API key is required"));
+        }
+
+        if let Some(stream_url) = &self.stream_url {
+            return self.process_http_stream_message(stream_url, user_message, on_delta).await;
+        }
+
+        // Crear URL con el mensaje del usuario como query
+        let encoded_message = js_sys::encode_uri_component(user_message);
+        let url = format!(
+            "{}?q={}&apiKey={}", 
+            CHAT_ENDPOINT, 
+            encoded_message.as_string().unwrap_or_default(),
+            self.api_key
+        );
+        
+        web_sys::console::log_1(&format!("Chat request URL: {}", url).into());
+
+        let resp = HttpHandler::get_request(&url).await?;
+        let status = resp.status();
+        
+        match status {
+            200 => {
+                // Leer el cuerpo de la respuesta
+                let text_promise = resp.text().map_err(|_| JsValue::from_str("Failed to read response"))?;
+                let text = JsFuture::from(text_promise).await?;
+                let json_text = text.as_string().unwrap_or_default();
+                
+                // Parsear la respuesta JSON
+                match serde_json::from_str::<NewsApiResponse>(&json_text) {
+                    Ok(news_response) => {
+                        if news_response.status == "ok" {
+                            self.format_news_response(&news_response)
+                        } else {
+                            Ok(format!("Lo siento, hubo un problema: {}", 
+                                news_response.message.unwrap_or("Error desconocido".to_string())))
+                        }
+                    },
+                    Err(_) => {
+                        web_sys::console::log_1(&format!("Failed to parse JSON response: {}", json_text).into());
+                        Ok("Lo siento, no pude procesar la respuesta del servidor.".to_string())
+                    }
+                }
+            },
+            _ => {
+                // Usar el handler centralizado para manejar errores de chat
+                Ok(HttpHandler::handle_chat_response(status, None))
+            }
+        }
+    }
+
+    // Procesar mensaje via HTTP leyendo el cuerpo a medida que llega
+    async fn process_http_stream_message<F>(&self, stream_url: &str, user_message: &str, on_delta: F) -> Result<String, JsValue>
+    where
+        F: FnMut(&str),
+    {
+        let encoded_message = js_sys::encode_uri_component(user_message);
+        let separator = if stream_url.contains('?') { '&' } else { '?' };
+        let url = format!(
+            "{}{}q={}&apiKey={}",
+            stream_url,
+            separator,
+            encoded_message.as_string().unwrap_or_default(),
+            self.api_key
+        );
+
+        let resp = HttpHandler::get_stream_request(&url).await?;
+        let status = resp.status();
+        if status != 200 {
+            return Ok(HttpHandler::handle_chat_response(status, None));
+        }
+
+        let content_type = resp.headers().get("Content-Type")?.unwrap_or_default();
+        match StreamFormat::from_content_type(&content_type) {
+            Some(format) => read_stream(&resp, format, on_delta).await,
+            // El servidor respondió de una vez: el cuerpo es la respuesta
+            None => {
+                let text_promise = resp.text().map_err(|_| JsValue::from_str("Failed to read response"))?;
+                let text = JsFuture::from(text_promise).await?;
+                Ok(text.as_string().unwrap_or_default())
+            }
+        }
+    }
+
+
+    // Formatear la respuesta de noticias en un mensaje amigable
+    fn format_news_response(&self, news_response: &NewsApiResponse) -> Result<String, JsValue> {
+        let total_results = news_response.total_results.unwrap_or(0);
+        
+        if total_results == 0 {
+            return Ok("No encontré noticias relacionadas con tu consulta. ¿Podrías probar con otros términos?".to_string());
+        }
+
+        let empty_vec = vec![];
+        let articles = news_response.articles.as_ref().unwrap_or(&empty_vec);
+        let limited_articles = articles.iter().take(3); // Mostrar solo las primeras 3 noticias
+        
+        let mut response = format!("Encontré {} noticias relacionadas. Aquí están las más relevantes:\n\n", total_results);
+        
+        for (index, article) in limited_articles.enumerate() {
+            response.push_str(&format!(
+                "{}. **{}**\n{}\n[Leer más]({})\n\n",
+                index + 1,
+                article.title,
+                article.description.as_ref().unwrap_or(&"Sin descripción disponible.".to_string()),
+                article.url
+            ));
+        }
+        
+        if total_results > 3 {
+            response.push_str(&format!("Y {} noticias más...", total_results - 3));
+        }
+        
+        Ok(response)
+    }
+}
+
+impl ChatTransport for HttpTransport {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn connect(&self) -> TransportFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn send<'a>(&'a self, _message_id: &'a str, content: &'a str, on_delta: &'a mut dyn FnMut(&str)) -> TransportFuture<'a, ChatReply> {
+        Box::pin(async move {
+            let text = self.process_http_message(content, on_delta).await?;
+            Ok(ChatReply { message_id: None, text })
+        })
+    }
+
+    // Sin canal de eventos: todo llega en la respuesta de cada petición
+    fn subscribe(&self, _listener: TransportListener) {}
+
+    fn close(&self) {}
+
+    fn state(&self) -> ConnectionState {
+        ConnectionState::Connected
+    }
+}
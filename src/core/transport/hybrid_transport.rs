@@ -0,0 +1,276 @@
+use wasm_bindgen::prelude::*;
+use std::cell::Cell;
+use std::rc::{Rc, Weak};
+use crate::core::transport::chat_transport::{ChatReply, ChatTransport, Receipt, TransportEvent, TransportFuture, TransportListener, TransportListeners};
+use crate::core::websocket::websocket_manager::{clear_timer, set_timer, ConnectionState};
+
+// Fallos seguidos del transporte activo antes de pasar al siguiente
+const FAILOVER_THRESHOLD: u32 = 3;
+
+// Cada cuánto se vuelve a probar un transporte preferido mientras se usa otro
+const PROBE_INTERVAL_MS: u32 = 15_000;
+
+#[derive(Debug)]
+struct HybridInner {
+    transports: Vec<Rc<dyn ChatTransport>>, // En orden de preferencia
+    active: Cell<Option<usize>>,            // Índice del transporte en uso
+    failures: Cell<u32>,                    // Fallos seguidos del transporte activo
+    probe_timer: Cell<Option<i32>>,
+    listeners: TransportListeners,
+}
+
+// Usa el transporte preferido que funcione. Tras fallos repetidos pasa al
+// siguiente y, mientras tanto, sigue probando los preferidos en segundo plano
+// para volver a ellos en cuanto se recuperan.
+#[derive(Debug, Clone)]
+pub struct HybridTransport {
+    inner: Rc<HybridInner>,
+}
+
+impl HybridInner {
+    fn active_transport(&self) -> Option<&Rc<dyn ChatTransport>> {
+        self.active.get().and_then(|index| self.transports.get(index))
+    }
+
+    fn set_active(self: &Rc<Self>, index: usize) {
+        let previous = self.active.get();
+        if previous == Some(index) {
+            return;
+        }
+
+        self.active.set(Some(index));
+        self.failures.set(0);
+
+        // El transporte que se deja se cierra para que no siga entregando frames;
+        // el sondeo lo vuelve a conectar si es uno de los preferidos
+        if let Some(previous) = previous {
+            self.transports[previous].close();
+        }
+
+        let transport = &self.transports[index];
+        web_sys::console::log_1(&format!("Hybrid transport using {}", transport.name()).into());
+        self.listeners.emit(TransportEvent::TransportChanged(transport.name()));
+        self.listeners.emit(TransportEvent::StateChanged(transport.state()));
+
+        // Fuera del transporte preferido: sondear para poder volver
+        if index > 0 {
+            Self::schedule_probe(self);
+        } else {
+            clear_timer(&self.probe_timer);
+        }
+    }
+
+    fn handle_event(self: &Rc<Self>, index: usize, event: TransportEvent) {
+        let TransportEvent::StateChanged(state) = &event else {
+            // Solo cuentan los frames del activo: otro transporte conectado (por
+            // ejemplo, mientras se sondea) repetiría los mismos mensajes
+            let from_active = self.active.get() == Some(index);
+            if from_active || !matches!(event, TransportEvent::Message(_) | TransportEvent::DeliveryStatus { .. }) {
+                self.listeners.emit(event);
+            }
+            return;
+        };
+
+        match self.active.get() {
+            Some(active) if active == index => {
+                match state {
+                    ConnectionState::Connected => self.failures.set(0),
+                    ConnectionState::Reconnecting | ConnectionState::Error(_) | ConnectionState::AuthFailed(_) => {
+                        self.record_failure(index);
+                    }
+                    _ => {}
+                }
+                self.listeners.emit(event);
+            }
+            // Un transporte preferido volvió a conectar por su cuenta
+            Some(active) if index < active && matches!(state, ConnectionState::Connected) => {
+                self.set_active(index);
+            }
+            _ => {}
+        }
+    }
+
+    fn record_failure(self: &Rc<Self>, index: usize) {
+        if self.active.get() != Some(index) {
+            return;
+        }
+
+        let failures = self.failures.get() + 1;
+        self.failures.set(failures);
+        if failures < FAILOVER_THRESHOLD || index + 1 >= self.transports.len() {
+            return;
+        }
+
+        web_sys::console::log_1(&format!("{} keeps failing, switching transport", self.transports[index].name()).into());
+        self.failures.set(0);
+        let inner = self.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = inner.connect_from(index + 1).await {
+                web_sys::console::log_1(&format!("No fallback transport available: {:?}", e).into());
+            }
+        });
+    }
+
+    // Conectar el primer transporte disponible a partir de `start` y activarlo
+    async fn connect_from(self: &Rc<Self>, start: usize) -> Result<usize, JsValue> {
+        let mut last_error = JsValue::from_str("No transport configured");
+
+        for (index, transport) in self.transports.iter().enumerate().skip(start) {
+            match transport.connect().await {
+                Ok(()) => {
+                    self.set_active(index);
+                    return Ok(index);
+                }
+                Err(e) => {
+                    web_sys::console::log_1(&format!("{} unavailable, falling back: {:?}", transport.name(), e).into());
+                    last_error = e;
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    fn schedule_probe(inner: &Rc<Self>) {
+        clear_timer(&inner.probe_timer);
+
+        let inner_ref: Weak<Self> = Rc::downgrade(inner);
+        let probe_callback = Closure::once_into_js(move || {
+            let Some(inner) = inner_ref.upgrade() else { return };
+            inner.probe_timer.set(None);
+            wasm_bindgen_futures::spawn_local(Self::probe(inner));
+        });
+        inner.probe_timer.set(set_timer(&probe_callback, PROBE_INTERVAL_MS));
+    }
+
+    // Probar los transportes preferidos al activo y volver al primero que conecte
+    async fn probe(inner: Rc<Self>) {
+        let Some(active) = inner.active.get() else { return };
+
+        for (index, transport) in inner.transports.iter().enumerate().take(active) {
+            if transport.connect().await.is_ok() {
+                // Mientras se probaba pudo cambiar el transporte activo
+                if inner.active.get().is_some_and(|current| index < current) {
+                    inner.set_active(index);
+                }
+                return;
+            }
+        }
+
+        if inner.active.get().is_some_and(|current| current > 0) {
+            Self::schedule_probe(&inner);
+        }
+    }
+}
+
+impl HybridTransport {
+    pub fn new(transports: Vec<Rc<dyn ChatTransport>>) -> Self {
+        let inner = Rc::new(HybridInner {
+            transports,
+            active: Cell::new(None),
+            failures: Cell::new(0),
+            probe_timer: Cell::new(None),
+            listeners: TransportListeners::default(),
+        });
+
+        for (index, transport) in inner.transports.iter().enumerate() {
+            let inner_ref = Rc::downgrade(&inner);
+            transport.subscribe(Box::new(move |event| {
+                if let Some(inner) = inner_ref.upgrade() {
+                    inner.handle_event(index, event);
+                }
+            }));
+        }
+
+        Self { inner }
+    }
+}
+
+impl ChatTransport for HybridTransport {
+    // El nombre del transporte en uso
+    fn name(&self) -> &'static str {
+        self.inner.active_transport().map_or("hybrid", |transport| transport.name())
+    }
+
+    fn connect(&self) -> TransportFuture<'_, ()> {
+        Box::pin(async move {
+            let start = self.inner.active.get().unwrap_or(0);
+            self.inner.connect_from(start).await?;
+            Ok(())
+        })
+    }
+
+    fn send<'a>(&'a self, message_id: &'a str, content: &'a str, on_delta: &'a mut dyn FnMut(&str)) -> TransportFuture<'a, ChatReply> {
+        Box::pin(async move {
+            // Si el activo no conecta, el mensaje sale por el siguiente que lo haga
+            let start = self.inner.active.get().unwrap_or(0);
+            let index = self.inner.connect_from(start).await?;
+
+            let result = self.inner.transports[index].send(message_id, content, on_delta).await;
+            match &result {
+                Ok(_) => self.inner.failures.set(0),
+                Err(_) => self.inner.record_failure(index),
+            }
+            result
+        })
+    }
+
+    fn send_typing(&self, is_typing: bool) {
+        if let Some(transport) = self.inner.active_transport() {
+            transport.send_typing(is_typing);
+        }
+    }
+
+    fn send_receipt(&self, message_id: &str, receipt: Receipt) {
+        if let Some(transport) = self.inner.active_transport() {
+            transport.send_receipt(message_id, receipt);
+        }
+    }
+
+    // El room vale para todos: el transporte activo puede cambiar en cualquier momento
+    fn set_room(&self, room: Option<String>) -> Result<(), JsValue> {
+        for transport in &self.inner.transports {
+            transport.set_room(room.clone())?;
+        }
+        Ok(())
+    }
+
+    fn edit_message(&self, message_id: &str, content: &str) -> Result<(), JsValue> {
+        match self.inner.active_transport() {
+            Some(transport) => transport.edit_message(message_id, content),
+            None => Err(JsValue::from_str("No active transport")),
+        }
+    }
+
+    fn delete_message(&self, message_id: &str) -> Result<(), JsValue> {
+        match self.inner.active_transport() {
+            Some(transport) => transport.delete_message(message_id),
+            None => Err(JsValue::from_str("No active transport")),
+        }
+    }
+
+    fn subscribe(&self, listener: TransportListener) {
+        self.inner.listeners.add(listener);
+    }
+
+    fn close(&self) {
+        clear_timer(&self.inner.probe_timer);
+        for transport in &self.inner.transports {
+            transport.close();
+        }
+        self.inner.active.set(None);
+    }
+
+    fn state(&self) -> ConnectionState {
+        self.inner.active_transport()
+            .or_else(|| self.inner.transports.first())
+            .map_or(ConnectionState::Disconnected, |transport| transport.state())
+    }
+}
+
+// Detener el sondeo cuando se suelta el último handle
+impl Drop for HybridInner {
+    fn drop(&mut self) {
+        clear_timer(&self.probe_timer);
+    }
+}
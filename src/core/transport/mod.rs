@@ -0,0 +1,7 @@
+pub mod backoff;
+pub mod chat_transport;
+pub mod connection_waiters;
+pub mod http_request;
+pub mod http_transport;
+pub mod hybrid_transport;
+pub mod reply_tracker;
\ No newline at end of file
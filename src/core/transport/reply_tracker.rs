@@ -0,0 +1,170 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use crate::core::transport::chat_transport::ChatReply;
+use crate::core::websocket::websocket_manager::{cancel_timer, set_timer, WebSocketMessage};
+
+// Mensaje enviado esperando su respuesta
+#[derive(Debug)]
+struct PendingReply {
+    resolve: js_sys::Function,
+    reject: js_sys::Function,
+    timer: Option<i32>,
+}
+
+// Respuesta en streaming en curso: el mensaje que responde y el texto recibido
+#[derive(Debug)]
+struct ReplyStream {
+    request_id: String,
+    content: String,
+}
+
+#[derive(Debug, Default)]
+struct TrackerState {
+    pending: HashMap<String, PendingReply>,
+    streams: HashMap<String, ReplyStream>, // Por message_id de la respuesta
+}
+
+// Relaciona los mensajes enviados con la respuesta que llega después por el
+// canal de eventos: un `ServerResponse` con datos (o de error), un `ChatMessage`
+// con `reply_to` o una respuesta en streaming completa. Un `ServerResponse` sin
+// datos es solo el acuse de recibo. Los clones comparten estado.
+#[derive(Debug, Clone)]
+pub struct ReplyTracker {
+    state: Rc<RefCell<TrackerState>>,
+    timeout_ms: u32,
+}
+
+impl ReplyTracker {
+    pub fn new(timeout_ms: u32) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(TrackerState::default())),
+            timeout_ms,
+        }
+    }
+
+    // Empezar a esperar la respuesta de un mensaje. Registrarlo antes de enviarlo
+    // para no perder una respuesta rápida.
+    pub fn wait(&self, message_id: &str) -> js_sys::Promise {
+        let mut callbacks = None;
+        let promise = js_sys::Promise::new(&mut |resolve, reject| {
+            callbacks = Some((resolve, reject));
+        });
+
+        if let Some((resolve, reject)) = callbacks {
+            self.state.borrow_mut().pending
+                .insert(message_id.to_string(), PendingReply { resolve, reject, timer: None });
+            self.arm_timeout(message_id);
+        }
+        promise
+    }
+
+    // Pasar un frame entrante. Devuelve `true` si era la respuesta a un mensaje
+    // pendiente y ya no hace falta emitirlo. Los frames de streaming siguen
+    // llegando a los suscriptores para pintar la burbuja.
+    pub fn handle(&self, message: &WebSocketMessage) -> bool {
+        let (request_id, reply) = match message {
+            // Sin datos y sin error es solo el acuse: la respuesta llega después
+            WebSocketMessage::ServerResponse { status, data: None, .. } if status != "error" => return false,
+            WebSocketMessage::ServerResponse { message_id, status, data } if status == "error" => {
+                let reason = data.clone().unwrap_or_else(|| status.clone());
+                return Self::reject_in(&self.state, message_id, &reason);
+            }
+            WebSocketMessage::ServerResponse { message_id, data, .. } => {
+                (message_id, ChatReply { message_id: None, text: data.clone().unwrap_or_default() })
+            }
+            WebSocketMessage::ChatMessage { id, content, reply_to: Some(reply_to), .. } => {
+                (reply_to, ChatReply { message_id: Some(id.clone()), text: content.clone() })
+            }
+            // Cada parte de una respuesta en streaming mantiene viva la espera
+            WebSocketMessage::MessageStart { message_id, reply_to: Some(reply_to) } => {
+                let stream = ReplyStream { request_id: reply_to.clone(), content: String::new() };
+                self.state.borrow_mut().streams.insert(message_id.clone(), stream);
+                self.arm_timeout(reply_to);
+                return false;
+            }
+            WebSocketMessage::MessageDelta { message_id, delta } => {
+                let request_id = self.state.borrow_mut().streams.get_mut(message_id).map(|stream| {
+                    stream.content.push_str(delta);
+                    stream.request_id.clone()
+                });
+                if let Some(request_id) = request_id {
+                    self.arm_timeout(&request_id);
+                }
+                return false;
+            }
+            // La respuesta en streaming se entrega completa, con el id de su burbuja
+            WebSocketMessage::MessageEnd { message_id } => {
+                let stream = self.state.borrow_mut().streams.remove(message_id);
+                let Some(stream) = stream else { return false };
+                let reply = ChatReply { message_id: Some(message_id.clone()), text: stream.content };
+                self.resolve(&stream.request_id, &reply);
+                return false;
+            }
+            _ => return false,
+        };
+
+        self.resolve(request_id, &reply)
+    }
+
+    pub fn reject(&self, message_id: &str, reason: &str) {
+        let _ = Self::reject_in(&self.state, message_id, reason);
+    }
+
+    pub fn reject_all(&self, reason: &str) {
+        let pending: Vec<PendingReply> = {
+            let mut state = self.state.borrow_mut();
+            state.streams.clear();
+            state.pending.drain().map(|(_, pending)| pending).collect()
+        };
+        for pending in pending {
+            cancel_timer(pending.timer);
+            let _ = pending.reject.call1(&JsValue::NULL, &JsValue::from_str(reason));
+        }
+    }
+
+    // Esperar la respuesta; un `status: "error"` llega como error
+    pub async fn reply(promise: js_sys::Promise) -> Result<ChatReply, JsValue> {
+        let response = JsFuture::from(promise).await?;
+        serde_json::from_str(&response.as_string().unwrap_or_default())
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse response: {}", e)))
+    }
+
+    // Resolver la espera de `request_id`. Devuelve `true` si había una.
+    fn resolve(&self, request_id: &str, reply: &ChatReply) -> bool {
+        let pending = self.state.borrow_mut().pending.remove(request_id);
+        let Some(pending) = pending else { return false };
+        cancel_timer(pending.timer);
+        if let Ok(json) = serde_json::to_string(reply) {
+            let _ = pending.resolve.call1(&JsValue::NULL, &JsValue::from_str(&json));
+        }
+        true
+    }
+
+    // (Re)iniciar el timeout de un mensaje pendiente
+    fn arm_timeout(&self, message_id: &str) {
+        let state_ref: Weak<RefCell<TrackerState>> = Rc::downgrade(&self.state);
+        let timeout_id = message_id.to_string();
+        let timeout_callback = Closure::once_into_js(move || {
+            if let Some(state) = state_ref.upgrade() {
+                let _ = Self::reject_in(&state, &timeout_id, "Request timed out");
+            }
+        });
+
+        if let Some(pending) = self.state.borrow_mut().pending.get_mut(message_id) {
+            cancel_timer(pending.timer.take());
+            pending.timer = set_timer(&timeout_callback, self.timeout_ms);
+        }
+    }
+
+    // Rechazar la espera de `message_id`. Devuelve `true` si había una.
+    fn reject_in(state: &RefCell<TrackerState>, message_id: &str, reason: &str) -> bool {
+        let pending = state.borrow_mut().pending.remove(message_id);
+        let Some(pending) = pending else { return false };
+        cancel_timer(pending.timer);
+        let _ = pending.reject.call1(&JsValue::NULL, &JsValue::from_str(reason));
+        true
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt;
+use super::websocket_manager::{SequencedFrame, WebSocketMessage};
+use super::wire_format;
+
+// Subprotocolos WebSocket con los que se negocia el codec al conectar
+pub const JSON_SUBPROTOCOL: &str = "grace.v2.json";
+pub const MESSAGEPACK_SUBPROTOCOL: &str = "grace.v2.msgpack";
+
+// Frame tal como viaja por el socket
+#[derive(Debug, Clone)]
+pub enum EncodedFrame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+// Codec de los frames. Se ofrece al servidor por su subprotocolo y se usa el que
+// este elija; los frames de texto se aceptan siempre, sea cual sea el codec.
+pub trait FrameCodec: fmt::Debug {
+    fn subprotocol(&self) -> &'static str;
+    fn encode(&self, message: &WebSocketMessage) -> Result<EncodedFrame, String>;
+    fn decode(&self, frame: &EncodedFrame) -> Result<SequencedFrame, String>;
+}
+
+// Formato actual en texto JSON; también el de servidores que no negocian subprotocolo
+#[derive(Debug, Default)]
+pub struct JsonCodec;
+
+impl FrameCodec for JsonCodec {
+    fn subprotocol(&self) -> &'static str {
+        JSON_SUBPROTOCOL
+    }
+
+    fn encode(&self, message: &WebSocketMessage) -> Result<EncodedFrame, String> {
+        wire_format::encode(message).map(EncodedFrame::Text)
+    }
+
+    fn decode(&self, frame: &EncodedFrame) -> Result<SequencedFrame, String> {
+        match frame {
+            EncodedFrame::Text(text) => wire_format::decode(text),
+            EncodedFrame::Binary(_) => Err("Received binary frame on a JSON connection".to_string()),
+        }
+    }
+}
+
+// Formato binario compacto, pensado para adjuntos y presencia de alta frecuencia
+#[derive(Debug, Default)]
+pub struct MessagePackCodec;
+
+impl FrameCodec for MessagePackCodec {
+    fn subprotocol(&self) -> &'static str {
+        MESSAGEPACK_SUBPROTOCOL
+    }
+
+    fn encode(&self, message: &WebSocketMessage) -> Result<EncodedFrame, String> {
+        wire_format::encode_binary(message).map(EncodedFrame::Binary)
+    }
+
+    fn decode(&self, frame: &EncodedFrame) -> Result<SequencedFrame, String> {
+        match frame {
+            EncodedFrame::Text(text) => wire_format::decode(text),
+            EncodedFrame::Binary(bytes) => wire_format::decode_binary(bytes),
+        }
+    }
+}
@@ -1 +1,6 @@
-pub mod websocket_manager;
\ No newline at end of file
+pub mod websocket_manager;
+pub mod websocket_chat_client;
+pub mod sequence_buffer;
+pub mod wire_format;
+pub mod codec;
+pub mod presence_store;
\ No newline at end of file
@@ -0,0 +1,311 @@
+use wasm_bindgen::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use super::websocket_manager::{clear_timer, set_timer, WeakWebSocketManager, WebSocketManager, WebSocketMessage};
+
+// Tiempo sin actividad antes de pasar a "away"
+pub const DEFAULT_IDLE_TIMEOUT_MS: u32 = 5 * 60_000;
+
+// Eventos que cuentan como actividad del usuario
+const ACTIVITY_EVENTS: [&str; 5] = ["mousemove", "keydown", "pointerdown", "scroll", "touchstart"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresenceStatus {
+    Online,
+    Away,
+    Offline,
+}
+
+impl PresenceStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PresenceStatus::Online => "online",
+            PresenceStatus::Away => "away",
+            PresenceStatus::Offline => "offline",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "online" => Some(PresenceStatus::Online),
+            "away" => Some(PresenceStatus::Away),
+            "offline" => Some(PresenceStatus::Offline),
+            _ => None,
+        }
+    }
+}
+
+// Lo que se sabe de un usuario: su estado y cuándo se tuvo noticia suya por última vez
+#[derive(Debug, Clone, PartialEq)]
+pub struct Presence {
+    pub status: PresenceStatus,
+    pub last_seen: u64, // Milisegundos desde epoch
+}
+
+type OnChangeCallback = Box<dyn FnMut(String, Presence)>;
+
+struct PresenceInner {
+    // Débil: los callbacks del manager guardan este registro
+    manager: WeakWebSocketManager,
+    user_id: String,
+
+    users: RefCell<HashMap<String, Presence>>,
+    on_change: RefCell<Option<OnChangeCallback>>,
+
+    // Estado propio, publicado al servidor
+    local_status: Cell<PresenceStatus>,
+    page_hidden: Cell<bool>,
+    last_activity: Cell<f64>,
+    idle_timeout_ms: Cell<u32>,
+    idle_timer: Cell<Option<i32>>,
+    tracking: Cell<bool>,
+}
+
+// Registro de presencia construido con los frames `user_presence`. También
+// publica el estado del usuario local, que pasa a "away" con la página oculta
+// o tras un rato sin actividad, y vuelve a "online" al regresar.
+// Los clones comparten el registro.
+#[derive(Clone)]
+pub struct PresenceStore {
+    inner: Rc<PresenceInner>,
+}
+
+impl PresenceInner {
+    fn set_local_status(&self, status: PresenceStatus) {
+        if self.local_status.get() == status {
+            return;
+        }
+        self.local_status.set(status);
+        self.publish();
+    }
+
+    // Enviar el estado propio; sin conexión se enviará al volver a conectar
+    fn publish(&self) {
+        let Some(manager) = self.manager.upgrade() else { return };
+        if !manager.is_connected() {
+            return;
+        }
+        if let Err(e) = manager.send_presence(self.local_status.get().as_str()) {
+            web_sys::console::log_1(&format!("Failed to publish presence: {:?}", e).into());
+        }
+    }
+
+    fn update(&self, user_id: &str, status: Option<PresenceStatus>) {
+        let presence = {
+            let mut users = self.users.borrow_mut();
+            let previous = users.get(user_id).cloned();
+            // Un mensaje de alguien sin presencia conocida indica que está conectado
+            let status = status
+                .or(previous.as_ref().map(|presence| presence.status))
+                .unwrap_or(PresenceStatus::Online);
+            let presence = Presence { status, last_seen: js_sys::Date::now() as u64 };
+            users.insert(user_id.to_string(), presence.clone());
+
+            // Solo los cambios de estado se notifican
+            if previous.is_some_and(|previous| previous.status == status) {
+                return;
+            }
+            presence
+        };
+
+        let callback = self.on_change.borrow_mut().take();
+        if let Some(mut callback) = callback {
+            callback(user_id.to_string(), presence);
+            let mut slot = self.on_change.borrow_mut();
+            if slot.is_none() {
+                *slot = Some(callback);
+            }
+        }
+    }
+
+    fn on_activity(inner: &Rc<Self>) {
+        inner.last_activity.set(js_sys::Date::now());
+        if !inner.page_hidden.get() && inner.local_status.get() != PresenceStatus::Online {
+            inner.set_local_status(PresenceStatus::Online);
+        }
+        if inner.idle_timer.get().is_none() {
+            Self::schedule_idle_check(inner, inner.idle_timeout_ms.get());
+        }
+    }
+
+    // La actividad no reinicia el timer: al vencer se mira cuánto hace de la
+    // última y, si no llegó al límite, se espera lo que falta
+    fn schedule_idle_check(inner: &Rc<Self>, delay_ms: u32) {
+        let inner_ref = Rc::downgrade(inner);
+        let idle_callback = Closure::once_into_js(move || {
+            let Some(inner) = inner_ref.upgrade() else { return };
+            inner.idle_timer.set(None);
+
+            let idle_ms = (js_sys::Date::now() - inner.last_activity.get()).max(0.0) as u32;
+            let timeout_ms = inner.idle_timeout_ms.get();
+            if idle_ms >= timeout_ms {
+                if inner.local_status.get() == PresenceStatus::Online {
+                    inner.set_local_status(PresenceStatus::Away);
+                }
+            } else {
+                Self::schedule_idle_check(&inner, timeout_ms - idle_ms);
+            }
+        });
+        inner.idle_timer.set(set_timer(&idle_callback, delay_ms));
+    }
+
+    fn on_visibility_change(inner: &Rc<Self>, hidden: bool) {
+        inner.page_hidden.set(hidden);
+        if hidden {
+            inner.set_local_status(PresenceStatus::Away);
+        } else {
+            Self::on_activity(inner);
+        }
+    }
+}
+
+impl PresenceStore {
+    pub fn new(manager: &WebSocketManager, user_id: String) -> Self {
+        Self {
+            inner: Rc::new(PresenceInner {
+                manager: manager.downgrade(),
+                user_id,
+                users: RefCell::new(HashMap::new()),
+                on_change: RefCell::new(None),
+                local_status: Cell::new(PresenceStatus::Online),
+                page_hidden: Cell::new(false),
+                last_activity: Cell::new(js_sys::Date::now()),
+                idle_timeout_ms: Cell::new(DEFAULT_IDLE_TIMEOUT_MS),
+                idle_timer: Cell::new(None),
+                tracking: Cell::new(false),
+            }),
+        }
+    }
+
+    // Cambios de estado de otros usuarios
+    pub fn on_change<F>(&self, callback: F)
+    where
+        F: FnMut(String, Presence) + 'static
+    {
+        *self.inner.on_change.borrow_mut() = Some(Box::new(callback));
+    }
+
+    pub fn get(&self, user_id: &str) -> Option<Presence> {
+        self.inner.users.borrow().get(user_id).cloned()
+    }
+
+    pub fn users(&self) -> HashMap<String, Presence> {
+        self.inner.users.borrow().clone()
+    }
+
+    pub fn local_status(&self) -> PresenceStatus {
+        self.inner.local_status.get()
+    }
+
+    // Fijar el estado propio a mano; la actividad y la visibilidad lo siguen cambiando
+    pub fn set_local_status(&self, status: PresenceStatus) {
+        self.inner.set_local_status(status);
+    }
+
+    pub fn set_idle_timeout(&self, timeout_ms: u32) {
+        self.inner.idle_timeout_ms.set(timeout_ms);
+    }
+
+    // Volver a publicar el estado propio, por ejemplo tras reconectar
+    pub fn publish(&self) {
+        self.inner.publish();
+    }
+
+    // Pasar un frame entrante. Devuelve `true` si era un frame de presencia.
+    pub fn handle(&self, message: &WebSocketMessage) -> bool {
+        match message {
+            WebSocketMessage::UserPresence { user_id, status } => {
+                if *user_id != self.inner.user_id {
+                    match PresenceStatus::parse(status) {
+                        Some(status) => self.inner.update(user_id, Some(status)),
+                        None => web_sys::console::log_1(&format!("Unknown presence status: {}", status).into()),
+                    }
+                }
+                true
+            }
+            // Cualquier mensaje de otro usuario actualiza cuándo se le vio
+            WebSocketMessage::ChatMessage { user_id, .. } | WebSocketMessage::RoomMessage { user_id, .. } => {
+                if *user_id != self.inner.user_id {
+                    self.inner.update(user_id, None);
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    // Empezar a seguir la visibilidad de la página y la actividad del usuario.
+    // Solo la primera llamada instala los listeners.
+    pub fn start_tracking(&self) -> Result<(), JsValue> {
+        if self.inner.tracking.replace(true) {
+            return Ok(());
+        }
+
+        let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window available"))?;
+        let document = window.document().ok_or_else(|| JsValue::from_str("No document available"))?;
+
+        // Visibilidad de la página
+        {
+            let inner_ref = Rc::downgrade(&self.inner);
+            let document_clone = document.clone();
+
+            let visibility_callback = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+                let Some(inner) = inner_ref.upgrade() else { return };
+                PresenceInner::on_visibility_change(&inner, document_clone.hidden());
+            }) as Box<dyn FnMut(_)>);
+
+            document.add_event_listener_with_callback("visibilitychange", visibility_callback.as_ref().unchecked_ref())?;
+            visibility_callback.forget();
+        }
+
+        // Actividad del usuario
+        {
+            let inner_ref = Rc::downgrade(&self.inner);
+
+            let activity_callback = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+                let Some(inner) = inner_ref.upgrade() else { return };
+                PresenceInner::on_activity(&inner);
+            }) as Box<dyn FnMut(_)>);
+
+            for event in ACTIVITY_EVENTS {
+                window.add_event_listener_with_callback(event, activity_callback.as_ref().unchecked_ref())?;
+            }
+            activity_callback.forget();
+        }
+
+        // Al salir de la página, avisar de que el usuario se fue
+        {
+            let inner_ref = Rc::downgrade(&self.inner);
+
+            let pagehide_callback = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+                let Some(inner) = inner_ref.upgrade() else { return };
+                inner.set_local_status(PresenceStatus::Offline);
+            }) as Box<dyn FnMut(_)>);
+
+            window.add_event_listener_with_callback("pagehide", pagehide_callback.as_ref().unchecked_ref())?;
+            pagehide_callback.forget();
+        }
+
+        PresenceInner::on_visibility_change(&self.inner, document.hidden());
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PresenceStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PresenceStore")
+            .field("user_id", &self.inner.user_id)
+            .field("local_status", &self.inner.local_status.get())
+            .field("users", &*self.inner.users.borrow())
+            .finish()
+    }
+}
+
+// Soltar el timer de inactividad con el último handle
+impl Drop for PresenceInner {
+    fn drop(&mut self) {
+        clear_timer(&self.idle_timer);
+    }
+}
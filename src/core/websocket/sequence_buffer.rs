@@ -0,0 +1,244 @@
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use super::websocket_manager::WebSocketMessage;
+
+// Frames fuera de orden que se retienen antes de dar por perdido el faltante
+pub const DEFAULT_REORDER_WINDOW: usize = 32;
+
+// Secuencias perdidas que se recuerdan para aceptarlas si llegan tarde
+const MAX_TRACKED_MISSING: usize = 1000;
+
+// Resultado de recibir un frame con número de secuencia
+#[derive(Debug, Default)]
+pub struct SequenceOutcome {
+    // Frames listos para entregar, ya en orden
+    pub ready: Vec<WebSocketMessage>,
+    // Rango (inclusivo) de secuencias que se dieron por perdidas
+    pub gap: Option<(u64, u64)>,
+    pub duplicate: bool,
+}
+
+// Reordena los frames entrantes según su secuencia, descarta duplicados
+// (por ejemplo, los reenviados al reanudar la sesión) y detecta huecos.
+#[derive(Debug)]
+pub struct SequenceBuffer {
+    last_delivered: Option<u64>,
+    pending: BTreeMap<u64, WebSocketMessage>,
+    missing: BTreeSet<u64>,
+    window: usize,
+}
+
+impl SequenceBuffer {
+    pub fn new(window: usize) -> Self {
+        Self {
+            last_delivered: None,
+            pending: BTreeMap::new(),
+            missing: BTreeSet::new(),
+            window,
+        }
+    }
+
+    // Última secuencia entregada en orden
+    pub fn last_delivered(&self) -> Option<u64> {
+        self.last_delivered
+    }
+
+    // Hay frames retenidos esperando a uno anterior
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    // Empezar de cero, por ejemplo con una sesión nueva
+    pub fn reset(&mut self) {
+        self.last_delivered = None;
+        self.pending.clear();
+        self.missing.clear();
+    }
+
+    pub fn push(&mut self, seq: u64, message: WebSocketMessage) -> SequenceOutcome {
+        let mut outcome = SequenceOutcome::default();
+
+        match self.last_delivered {
+            // Primer frame de la sesión: marca el inicio de la secuencia
+            None => {
+                self.last_delivered = Some(seq);
+                outcome.ready.push(message);
+            }
+            Some(last) if seq <= last => {
+                // Un frame que se dio por perdido y llega tarde (o por el historial)
+                if self.missing.remove(&seq) {
+                    outcome.ready.push(message);
+                } else {
+                    outcome.duplicate = true;
+                }
+            }
+            Some(last) if seq == last + 1 => {
+                self.last_delivered = Some(seq);
+                outcome.ready.push(message);
+                self.drain_consecutive(&mut outcome.ready);
+            }
+            Some(_) => {
+                match self.pending.entry(seq) {
+                    Entry::Occupied(_) => outcome.duplicate = true,
+                    Entry::Vacant(entry) => {
+                        entry.insert(message);
+                        if self.pending.len() > self.window {
+                            outcome.gap = self.skip_gap(&mut outcome.ready);
+                        }
+                    }
+                }
+            }
+        }
+
+        outcome
+    }
+
+    // Dejar de esperar los frames que faltan y entregar lo retenido
+    pub fn flush(&mut self) -> SequenceOutcome {
+        let mut outcome = SequenceOutcome::default();
+        outcome.gap = self.skip_gap(&mut outcome.ready);
+        outcome
+    }
+
+    // Dar por perdidos los frames anteriores al primero retenido
+    fn skip_gap(&mut self, ready: &mut Vec<WebSocketMessage>) -> Option<(u64, u64)> {
+        let first = *self.pending.keys().next()?;
+        let from = self.last_delivered.map_or(first, |last| last + 1);
+
+        let gap = if from < first { Some((from, first - 1)) } else { None };
+        if let Some((from, to)) = gap {
+            let start = from.max(to.saturating_sub(MAX_TRACKED_MISSING as u64 - 1));
+            self.missing.extend(start..=to);
+            while self.missing.len() > MAX_TRACKED_MISSING {
+                self.missing.pop_first();
+            }
+        }
+
+        self.last_delivered = Some(first - 1);
+        self.drain_consecutive(ready);
+        gap
+    }
+
+    fn drain_consecutive(&mut self, ready: &mut Vec<WebSocketMessage>) {
+        while let Some(last) = self.last_delivered {
+            match self.pending.remove(&(last + 1)) {
+                Some(message) => {
+                    ready.push(message);
+                    self.last_delivered = Some(last + 1);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Frame identificable por su secuencia
+    fn frame(seq: u64) -> WebSocketMessage {
+        WebSocketMessage::Ping { timestamp: seq }
+    }
+
+    fn timestamps(messages: &[WebSocketMessage]) -> Vec<u64> {
+        messages.iter()
+            .map(|message| match message {
+                WebSocketMessage::Ping { timestamp } => *timestamp,
+                other => panic!("unexpected message: {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn delivers_in_order_frames_immediately() {
+        let mut buffer = SequenceBuffer::new(DEFAULT_REORDER_WINDOW);
+
+        assert_eq!(timestamps(&buffer.push(1, frame(1)).ready), vec![1]);
+        assert_eq!(timestamps(&buffer.push(2, frame(2)).ready), vec![2]);
+        assert_eq!(buffer.last_delivered(), Some(2));
+        assert!(!buffer.has_pending());
+    }
+
+    #[test]
+    fn holds_out_of_order_frames_until_the_missing_one_arrives() {
+        let mut buffer = SequenceBuffer::new(DEFAULT_REORDER_WINDOW);
+        buffer.push(1, frame(1));
+
+        let outcome = buffer.push(3, frame(3));
+        assert!(outcome.ready.is_empty());
+        assert!(buffer.has_pending());
+
+        let outcome = buffer.push(2, frame(2));
+        assert_eq!(timestamps(&outcome.ready), vec![2, 3]);
+        assert_eq!(outcome.gap, None);
+        assert!(!buffer.has_pending());
+    }
+
+    #[test]
+    fn drops_duplicates_of_delivered_and_pending_frames() {
+        let mut buffer = SequenceBuffer::new(DEFAULT_REORDER_WINDOW);
+        buffer.push(1, frame(1));
+        buffer.push(3, frame(3));
+
+        let delivered_again = buffer.push(1, frame(1));
+        assert!(delivered_again.duplicate);
+        assert!(delivered_again.ready.is_empty());
+
+        let pending_again = buffer.push(3, frame(3));
+        assert!(pending_again.duplicate);
+        assert!(pending_again.ready.is_empty());
+    }
+
+    #[test]
+    fn skips_the_gap_when_the_reorder_window_overflows() {
+        let mut buffer = SequenceBuffer::new(2);
+        buffer.push(1, frame(1));
+        buffer.push(4, frame(4));
+        buffer.push(5, frame(5));
+
+        let outcome = buffer.push(6, frame(6));
+        assert_eq!(outcome.gap, Some((2, 3)));
+        assert_eq!(timestamps(&outcome.ready), vec![4, 5, 6]);
+        assert_eq!(buffer.last_delivered(), Some(6));
+    }
+
+    #[test]
+    fn flush_gives_up_on_missing_frames() {
+        let mut buffer = SequenceBuffer::new(DEFAULT_REORDER_WINDOW);
+        buffer.push(1, frame(1));
+        buffer.push(3, frame(3));
+
+        let outcome = buffer.flush();
+        assert_eq!(outcome.gap, Some((2, 2)));
+        assert_eq!(timestamps(&outcome.ready), vec![3]);
+        assert!(!buffer.has_pending());
+    }
+
+    #[test]
+    fn accepts_a_skipped_frame_once_when_it_arrives_late() {
+        let mut buffer = SequenceBuffer::new(DEFAULT_REORDER_WINDOW);
+        buffer.push(1, frame(1));
+        buffer.push(3, frame(3));
+        buffer.flush();
+
+        let late = buffer.push(2, frame(2));
+        assert_eq!(timestamps(&late.ready), vec![2]);
+        assert!(!late.duplicate);
+
+        let again = buffer.push(2, frame(2));
+        assert!(again.duplicate);
+    }
+
+    #[test]
+    fn reset_starts_a_new_sequence() {
+        let mut buffer = SequenceBuffer::new(DEFAULT_REORDER_WINDOW);
+        buffer.push(10, frame(10));
+        buffer.push(12, frame(12));
+        buffer.reset();
+
+        assert_eq!(buffer.last_delivered(), None);
+        assert!(!buffer.has_pending());
+        assert_eq!(timestamps(&buffer.push(1, frame(1)).ready), vec![1]);
+    }
+}
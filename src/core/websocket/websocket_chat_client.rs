@@ -0,0 +1,224 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use std::cell::RefCell;
+use std::rc::Rc;
+use super::websocket_manager::{AuthCredentials, ConnectionState, DeliveryStatus, WebSocketManager, WebSocketMessage};
+use super::presence_store::PresenceStore;
+use crate::core::transport::chat_transport::{ChatReply, ChatTransport, Receipt, TransportEvent, TransportFuture, TransportListener, TransportListeners};
+use crate::core::transport::connection_waiters::ConnectionWaiters;
+use crate::core::transport::reply_tracker::ReplyTracker;
+
+// Tiempo máximo de espera para abrir la conexión
+const CONNECT_TIMEOUT_MS: u32 = 10_000;
+
+// Tiempo máximo de espera por la respuesta del servidor
+const REPLY_TIMEOUT_MS: u32 = 30_000;
+
+// Cliente de chat sobre WebSocket: mantiene una conexión de larga duración
+// y resuelve cada mensaje enviado con la respuesta del servidor.
+// Los clones comparten la misma conexión.
+#[derive(Debug, Clone)]
+pub struct WebSocketChatClient {
+    manager: WebSocketManager,
+    user_id: String,
+    room: Rc<RefCell<Option<String>>>, // Conversación separada, por ejemplo una por página de producto
+    connection_waiters: ConnectionWaiters,
+    replies: ReplyTracker,
+    listeners: TransportListeners,
+    presence: PresenceStore,
+}
+
+impl WebSocketChatClient {
+    pub fn new(url: String, user_id: String, credentials: Option<AuthCredentials>) -> Self {
+        let manager = WebSocketManager::new(url);
+        manager.set_credentials(credentials);
+        let connection_waiters = ConnectionWaiters::default();
+        let replies = ReplyTracker::new(REPLY_TIMEOUT_MS);
+        let listeners = TransportListeners::default();
+        let presence = PresenceStore::new(&manager, user_id.clone());
+
+        // Liberar a quienes esperan la conexión
+        {
+            let connection_waiters = connection_waiters.clone();
+            let listeners = listeners.clone();
+            let presence = presence.clone();
+
+            manager.on_state_change(move |state| {
+                match &state {
+                    ConnectionState::Connected => {
+                        connection_waiters.resolve_all();
+                        // El servidor olvida la presencia al cerrarse la conexión
+                        presence.publish();
+                    }
+                    // Sin reconexión tras un fallo de auth: no tiene sentido seguir esperando
+                    ConnectionState::AuthFailed(reason) => connection_waiters.reject_all(reason),
+                    _ => {}
+                }
+                listeners.emit(TransportEvent::StateChanged(state));
+            });
+        }
+
+        // Reenviar los frames y los estados de entrega a los suscriptores.
+        // Las respuestas a los mensajes enviados y los de presencia se quedan aquí.
+        {
+            let listeners = listeners.clone();
+            let presence = presence.clone();
+            let replies = replies.clone();
+            manager.on_message(move |message| {
+                if !replies.handle(&message) && !presence.handle(&message) {
+                    listeners.emit(TransportEvent::Message(message));
+                }
+            });
+        }
+        {
+            let listeners = listeners.clone();
+            let replies = replies.clone();
+            manager.on_delivery_status(move |message_id, status| {
+                // Si se renuncia a entregar el mensaje, su respuesta no va a llegar
+                if let DeliveryStatus::Failed(reason) = &status {
+                    replies.reject(&message_id, reason);
+                }
+                listeners.emit(TransportEvent::DeliveryStatus { message_id, status });
+            });
+        }
+
+        Self {
+            manager,
+            user_id,
+            room: Rc::new(RefCell::new(None)),
+            connection_waiters,
+            replies,
+            listeners,
+            presence,
+        }
+    }
+
+    // Manager subyacente, para enviar otros frames. Los eventos se reciben con `subscribe`.
+    pub fn manager(&self) -> &WebSocketManager {
+        &self.manager
+    }
+
+    // Presencia de los demás usuarios y del usuario local
+    pub fn presence(&self) -> &PresenceStore {
+        &self.presence
+    }
+
+    // Cambiar de room: se sale del anterior y los mensajes siguientes van al nuevo
+    pub fn set_room(&self, room: Option<String>) -> Result<(), JsValue> {
+        if *self.room.borrow() == room {
+            return Ok(());
+        }
+        
+        // El room guardado cambia solo cuando el manager acepta cada paso
+        let previous = self.room.borrow().clone();
+        if let Some(previous) = previous {
+            self.manager.leave_room(&previous)?;
+            *self.room.borrow_mut() = None;
+        }
+        if let Some(room_id) = &room {
+            self.manager.join_room(room_id.clone())?;
+        }
+        *self.room.borrow_mut() = room;
+        Ok(())
+    }
+
+    // Abrir la conexión (si hace falta) y esperar a que esté lista
+    pub async fn connect(&self) -> Result<(), JsValue> {
+        if let Err(e) = self.presence.start_tracking() {
+            web_sys::console::log_1(&format!("Presence tracking unavailable: {:?}", e).into());
+        }
+
+        match self.manager.get_connection_state() {
+            ConnectionState::Connected => return Ok(()),
+            ConnectionState::Connecting | ConnectionState::Authenticating | ConnectionState::Reconnecting => {}
+            // Las credenciales pueden haberse renovado desde el fallo
+            ConnectionState::Disconnected | ConnectionState::AuthFailed(_) | ConnectionState::Error(_) => {
+                self.manager.connect(self.user_id.clone()).await?;
+            }
+        }
+
+        JsFuture::from(self.connection_waiters.wait(CONNECT_TIMEOUT_MS)).await?;
+        Ok(())
+    }
+
+    // Enviar un mensaje de chat y esperar la respuesta del bot. El `ServerResponse`
+    // sin datos solo confirma la entrega; la respuesta llega después. Una caída
+    // no la cancela: el mensaje sin acuse se reenvía al reconectar.
+    pub async fn send_chat_message(&self, message_id: &str, content: &str) -> Result<ChatReply, JsValue> {
+        self.connect().await?;
+
+        let message = self.manager.room_chat_message(message_id.to_string(), content.to_string(), self.room.borrow().clone())?;
+        // Registrar la espera antes de enviar para no perder una respuesta rápida
+        let promise = self.replies.wait(message_id);
+        if let Err(e) = self.manager.send_message(message) {
+            let reason = e.as_string().unwrap_or_else(|| "Failed to send message".to_string());
+            self.replies.reject(message_id, &reason);
+        }
+
+        ReplyTracker::reply(promise).await
+    }
+}
+
+impl ChatTransport for WebSocketChatClient {
+    fn name(&self) -> &'static str {
+        "websocket"
+    }
+
+    fn connect(&self) -> TransportFuture<'_, ()> {
+        Box::pin(WebSocketChatClient::connect(self))
+    }
+
+    // Las respuestas en streaming llegan como frames `message_delta`
+    fn send<'a>(&'a self, message_id: &'a str, content: &'a str, _on_delta: &'a mut dyn FnMut(&str)) -> TransportFuture<'a, ChatReply> {
+        Box::pin(self.send_chat_message(message_id, content))
+    }
+
+    fn send_typing(&self, is_typing: bool) {
+        // Sin conexión no se encola, así que el error no importa
+        let _ = self.manager.send_typing_indicator(is_typing);
+    }
+
+    fn set_room(&self, room: Option<String>) -> Result<(), JsValue> {
+        WebSocketChatClient::set_room(self, room)
+    }
+
+    // Los acuses sí se encolan: que el mensaje se leyó sigue siendo cierto al reconectar
+    fn send_receipt(&self, message_id: &str, receipt: Receipt) {
+        if let Err(e) = self.manager.send_message(receipt.frame(message_id, &self.user_id)) {
+            web_sys::console::log_1(&format!("Failed to send receipt: {:?}", e).into());
+        }
+    }
+
+    // Como los acuses, las ediciones se encolan sin conexión
+    fn edit_message(&self, message_id: &str, content: &str) -> Result<(), JsValue> {
+        self.manager.send_message(WebSocketMessage::MessageEdited {
+            message_id: message_id.to_string(),
+            user_id: self.user_id.clone(),
+            content: content.to_string(),
+            timestamp: js_sys::Date::now() as u64,
+        })
+    }
+
+    fn delete_message(&self, message_id: &str) -> Result<(), JsValue> {
+        self.manager.send_message(WebSocketMessage::MessageDeleted {
+            message_id: message_id.to_string(),
+            user_id: self.user_id.clone(),
+            timestamp: js_sys::Date::now() as u64,
+        })
+    }
+
+    fn subscribe(&self, listener: TransportListener) {
+        self.listeners.add(listener);
+    }
+
+    fn close(&self) {
+        if let Err(e) = self.manager.disconnect() {
+            web_sys::console::log_1(&format!("WebSocket disconnect failed: {:?}", e).into());
+        }
+        self.replies.reject_all("Disconnected");
+    }
+
+    fn state(&self) -> ConnectionState {
+        self.manager.get_connection_state()
+    }
+}
@@ -1,10 +1,19 @@
 use wasm_bindgen::prelude::*;
-use web_sys::{WebSocket, MessageEvent, CloseEvent, ErrorEvent};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{WebSocket, MessageEvent, CloseEvent, ErrorEvent, BinaryType};
 use serde::{Deserialize, Serialize};
-use std::rc::Rc;
-use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use super::sequence_buffer::{SequenceBuffer, SequenceOutcome, DEFAULT_REORDER_WINDOW};
+use super::codec::{EncodedFrame, FrameCodec, JsonCodec, MessagePackCodec};
+use crate::core::transport::backoff::backoff_delay;
 
+// En el cable cada frame lleva su tipo en `type`, en snake_case
+// (ver `wire_format` para el sobre con la versión)
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum WebSocketMessage {
     // Mensajes del chat
     ChatMessage {
@@ -12,22 +21,129 @@ pub enum WebSocketMessage {
         content: String,
         user_id: String,
         timestamp: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        room_id: Option<String>, // Sin room: conversación directa con el bot
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reply_to: Option<String>, // En las respuestas, id del ChatMessage que responden
+    },
+    // Rooms: suscribirse o salir de una conversación compartida
+    JoinRoom {
+        room_id: String,
+    },
+    LeaveRoom {
+        room_id: String,
+    },
+    // Mensaje publicado en un room por otro participante
+    RoomMessage {
+        room_id: String,
+        id: String,
+        content: String,
+        user_id: String,
+        timestamp: u64,
     },
     // Estados del usuario
     UserTyping {
         user_id: String,
         is_typing: bool,
     },
-    // Estado de conexión
+    // Autenticación, enviada justo después de abrir el socket
+    Auth {
+        api_key: Option<String>,
+        token: Option<String>,
+    },
+    AuthOk {
+        user_id: Option<String>,
+    },
+    // Estado de conexión. El cliente lo envía sin session_id al abrir una
+    // sesión nueva, con su versión del protocolo y lo que soporta; el servidor
+    // responde con el session_id que asignó, su versión y las capacidades acordadas.
     Connected {
+        user_id: String,
+        session_id: Option<String>,
+        #[serde(default)]
+        protocol_version: Option<u32>, // Ausente en servidores anteriores a la negociación
+        #[serde(default)]
+        capabilities: Vec<Capability>,
+    },
+    // Reanudar una sesión tras reconectar; el servidor reenvía lo que se
+    // perdió después de last_sequence y responde con `Connected`
+    ResumeSession {
         user_id: String,
         session_id: String,
+        last_sequence: Option<u64>,
+        #[serde(default)]
+        protocol_version: Option<u32>,
+        #[serde(default)]
+        capabilities: Vec<Capability>,
+    },
+    // Pedir al servidor que reenvíe un rango de frames perdidos
+    HistoryRequest {
+        session_id: Option<String>,
+        from_sequence: u64,
+        to_sequence: u64,
     },
     // Presencia de usuarios
     UserPresence {
         user_id: String,
         status: String, // "online", "away", "offline"
     },
+    // Respuesta del bot en streaming: se anuncia, llega por partes y se cierra.
+    // reply_to es el id del ChatMessage que responde.
+    MessageStart {
+        message_id: String,
+        #[serde(default)]
+        reply_to: Option<String>,
+    },
+    MessageDelta {
+        message_id: String,
+        delta: String,
+    },
+    MessageEnd {
+        message_id: String,
+    },
+    // Acuses de recibo y de lectura de un mensaje; user_id es quien lo recibió o lo vio
+    MessageDelivered {
+        message_id: String,
+        user_id: String,
+        timestamp: u64,
+    },
+    MessageRead {
+        message_id: String,
+        user_id: String,
+        timestamp: u64,
+    },
+    // Edición o retirada de un mensaje ya enviado; message_id es el id de su ChatMessage
+    MessageEdited {
+        message_id: String,
+        user_id: String,
+        content: String,
+        timestamp: u64,
+    },
+    MessageDeleted {
+        message_id: String,
+        user_id: String,
+        timestamp: u64,
+    },
+    // Traspaso de la conversación a una persona. El servidor anuncia la espera
+    // (y la repite cuando avanza la cola) y después la entrada y salida del agente.
+    HandoffRequested {
+        #[serde(default)]
+        reason: Option<String>,
+        #[serde(default)]
+        queue_position: Option<u32>, // 1 = el siguiente en ser atendido
+    },
+    AgentJoined {
+        agent_id: String,
+        agent_name: String,
+        #[serde(default)]
+        avatar_url: Option<String>,
+    },
+    AgentLeft {
+        agent_id: String,
+        agent_name: String,
+        #[serde(default)]
+        avatar_url: Option<String>,
+    },
     // Respuestas del servidor
     ServerResponse {
         message_id: String,
@@ -39,126 +155,930 @@ pub enum WebSocketMessage {
         code: String,
         message: String,
     },
+    // Heartbeat para detectar conexiones muertas
+    Ping {
+        timestamp: u64,
+    },
+    Pong {
+        timestamp: u64,
+    },
+}
+
+impl WebSocketMessage {
+    // Generar un id único para un mensaje de chat del usuario
+    pub fn new_id(user_id: &str) -> String {
+        format!(
+            "{}-{}-{}",
+            user_id,
+            js_sys::Date::now() as u64,
+            (js_sys::Math::random() * 1_000_000.0) as u32
+        )
+    }
+
+    // Id que permite relacionar el mensaje con su `ServerResponse`
+    pub fn message_id(&self) -> Option<&str> {
+        match self {
+            WebSocketMessage::ChatMessage { id, .. } => Some(id),
+            _ => None,
+        }
+    }
+
+    // Usuario que originó el frame, para descartar los propios que el servidor reenvía
+    pub fn sender_id(&self) -> Option<&str> {
+        match self {
+            WebSocketMessage::ChatMessage { user_id, .. }
+            | WebSocketMessage::RoomMessage { user_id, .. }
+            | WebSocketMessage::UserTyping { user_id, .. }
+            | WebSocketMessage::UserPresence { user_id, .. }
+            | WebSocketMessage::MessageDelivered { user_id, .. }
+            | WebSocketMessage::MessageRead { user_id, .. }
+            | WebSocketMessage::MessageEdited { user_id, .. }
+            | WebSocketMessage::MessageDeleted { user_id, .. } => Some(user_id),
+            _ => None,
+        }
+    }
+
+    // Room al que pertenece el frame, para enrutarlo a sus listeners
+    pub fn room_id(&self) -> Option<&str> {
+        match self {
+            WebSocketMessage::ChatMessage { room_id, .. } => room_id.as_deref(),
+            WebSocketMessage::JoinRoom { room_id }
+            | WebSocketMessage::LeaveRoom { room_id }
+            | WebSocketMessage::RoomMessage { room_id, .. } => Some(room_id),
+            _ => None,
+        }
+    }
+}
+
+// Funcionalidades opcionales del protocolo que ambos lados deben acordar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    // El servidor confirma cada mensaje y descarta los reenviados por id
+    Acks,
+    Streaming,
+    Rooms,
+    // Capacidades de servidores más nuevos que este SDK no conoce
+    #[serde(other)]
+    Unknown,
+}
+
+// Versión del protocolo que habla este SDK
+pub const PROTOCOL_VERSION: u32 = 2;
+
+// Versión que se asume cuando el servidor no envía `protocol_version`
+const LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+// Capacidades que este SDK ofrece en el handshake
+const SUPPORTED_CAPABILITIES: &[Capability] = &[Capability::Acks, Capability::Streaming, Capability::Rooms];
+
+// Código de error cuando cliente y servidor no tienen una versión en común
+pub const PROTOCOL_UNSUPPORTED: &str = "protocol_unsupported";
+
+// Frame entrante con su número de secuencia. Los frames de control
+// (heartbeat, handshake) pueden venir sin secuencia.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequencedFrame {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    #[serde(flatten)]
+    pub message: WebSocketMessage,
 }
 
 #[derive(Debug, Clone)]
 pub enum ConnectionState {
     Disconnected,
     Connecting,
+    Authenticating,
     Connected,
     Reconnecting,
+    AuthFailed(String),
     Error(String),
 }
 
+// Credenciales para autenticar el WebSocket
+#[derive(Debug, Clone)]
+pub enum AuthCredentials {
+    ApiKey(String),
+    Token(String), // Token de corta duración emitido por el backend del sitio
+}
+
+// Estado de entrega de un mensaje de chat enviado por el usuario
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeliveryStatus {
+    Pending,      // En cola, esperando conexión
+    Sent,         // Enviado por el socket, sin acuse del servidor
+    Acknowledged, // El servidor confirmó la recepción con un ServerResponse
+    Delivered,    // El destinatario lo recibió (`message_delivered`)
+    Read,         // El destinatario lo vio (`message_read`)
+    Failed(String),
+}
+
+// Límites por defecto de la cola de salida mientras no hay conexión
+const DEFAULT_QUEUE_MAX_SIZE: usize = 50;
+const DEFAULT_QUEUE_MAX_AGE_MS: u32 = 60_000;
+
+// Valores por defecto del heartbeat
+const DEFAULT_HEARTBEAT_INTERVAL_MS: u32 = 25_000;
+const DEFAULT_HEARTBEAT_TIMEOUT_MS: u32 = 10_000;
+
+// Tiempo que se espera un frame faltante antes de darlo por perdido
+const REORDER_TIMEOUT_MS: u32 = 2_000;
+
+// Código de cierre normal definido por el protocolo WebSocket
+const NORMAL_CLOSURE_CODE: u16 = 1000;
+
+// Tiempo máximo de espera por `AuthOk`
+const AUTH_TIMEOUT_MS: u32 = 10_000;
+
+// Códigos de cierre propios
+const HEARTBEAT_TIMEOUT_CODE: u16 = 4000;
+const AUTH_FAILED_CODE: u16 = 4001;
+const AUTH_TIMEOUT_CODE: u16 = 4002;
+
 // Callback traits para diferentes eventos
 type OnMessageCallback = Box<dyn FnMut(WebSocketMessage)>;
 type OnStateChangeCallback = Box<dyn FnMut(ConnectionState)>;
 type OnErrorCallback = Box<dyn FnMut(String)>;
+type OnDeliveryStatusCallback = Box<dyn FnMut((String, DeliveryStatus))>;
+type CallbackSlot<T> = RefCell<Option<Box<dyn FnMut(T)>>>;
 
-pub struct WebSocketManager {
-    websocket: Option<WebSocket>,
+// Mensaje en espera de que la conexión vuelva a abrirse
+struct QueuedMessage {
+    message: WebSocketMessage,
+    queued_at: f64,
+}
+
+// Mensaje de chat sin acuse del servidor. Los ya enviados se reenvían
+// con el mismo id al reconectar, y el servidor descarta los duplicados.
+struct TrackedMessage {
+    message: WebSocketMessage,
+    sent: bool,
+}
+
+// Petición enviada con `request` que espera su `ServerResponse`
+struct PendingRequest {
+    resolve: js_sys::Function,
+    reject: js_sys::Function,
+    timer: Option<i32>,
+    timeout_ms: u32,
+    last_activity: f64, // El timeout cuenta desde aquí; un streaming lo mantiene vivo
+}
+
+// Estado de la conexión y callbacks compartidos con los closures del WebSocket.
+// Los closures guardan un `Weak` para que soltar el manager cierre la conexión.
+struct ConnectionInner {
     url: String,
-    connection_state: Rc<RefCell<ConnectionState>>,
-    auto_reconnect: bool,
-    reconnect_interval: u32, // milliseconds
-    max_reconnect_attempts: u32,
-    current_reconnect_attempts: u32,
+    websocket: RefCell<Option<WebSocket>>,
+    connection_state: RefCell<ConnectionState>,
+    auto_reconnect: Cell<bool>,
+    reconnect_interval: Cell<u32>, // milliseconds
+    max_reconnect_attempts: Cell<u32>,
+    current_reconnect_attempts: Cell<u32>,
+    reconnect_timer: Cell<Option<i32>>,
     
-    // Callbacks
-    on_message: Option<OnMessageCallback>,
-    on_state_change: Option<OnStateChangeCallback>,
-    on_error: Option<OnErrorCallback>,
+    // Heartbeat
+    heartbeat_interval_ms: Cell<u32>,
+    heartbeat_timeout_ms: Cell<u32>,
+    heartbeat_timer: Cell<Option<i32>>,
+    pong_timer: Cell<Option<i32>>,
+    
+    // Peticiones esperando respuesta, por message_id
+    pending_requests: RefCell<HashMap<String, PendingRequest>>,
+    streams: RefCell<HashMap<String, String>>, // Respuesta en streaming -> petición que responde
+    
+    // Mensajes de chat esperando acuse, en orden de envío
+    unacked_messages: RefCell<Vec<TrackedMessage>>,
+    
+    // Cola de salida
+    outbound_queue: RefCell<VecDeque<QueuedMessage>>,
+    queue_max_size: Cell<usize>,
+    queue_max_age_ms: Cell<u32>,
+    
+    // Autenticación
+    credentials: RefCell<Option<AuthCredentials>>,
+    auth_timer: Cell<Option<i32>>,
     
     // Session info
-    user_id: Option<String>,
-    session_id: Option<String>,
+    user_id: RefCell<Option<String>>,
+    session_id: RefCell<Option<String>>, // Asignado por el servidor
+    
+    // Codecs ofrecidos al conectar, por orden de preferencia, y el acordado
+    codecs: RefCell<Vec<Rc<dyn FrameCodec>>>,
+    codec: RefCell<Rc<dyn FrameCodec>>,
+    protocol_version: Cell<Option<u32>>, // Acordada en el último handshake
+    capabilities: RefCell<Vec<Capability>>,
+    sequence_buffer: RefCell<SequenceBuffer>,
+    reorder_timer: Cell<Option<i32>>,
+    
+    // Rooms suscritos; se restauran al reconectar
+    rooms: RefCell<BTreeSet<String>>,
+    
+    // Callbacks
+    on_message: RefCell<Option<OnMessageCallback>>,
+    on_state_change: RefCell<Option<OnStateChangeCallback>>,
+    on_error: RefCell<Option<OnErrorCallback>>,
+    on_delivery_status: RefCell<Option<OnDeliveryStatusCallback>>,
+    on_room_message: RefCell<HashMap<String, Rc<CallbackSlot<WebSocketMessage>>>>,
+}
+
+// Handle a una conexión WebSocket. Clonarlo comparte la misma conexión;
+// el socket se cierra cuando se suelta el último handle.
+#[derive(Clone)]
+pub struct WebSocketManager {
+    inner: Rc<ConnectionInner>,
+}
+
+// Handle que no mantiene viva la conexión, para quien se registra en los
+// callbacks del manager y a la vez lo usa (evita un ciclo de `Rc`)
+#[derive(Clone)]
+pub struct WeakWebSocketManager {
+    inner: Weak<ConnectionInner>,
+}
+
+impl WeakWebSocketManager {
+    pub fn upgrade(&self) -> Option<WebSocketManager> {
+        self.inner.upgrade().map(|inner| WebSocketManager { inner })
+    }
+}
+
+impl ConnectionInner {
+    // Cambiar estado de conexión y notificar al listener
+    fn set_state(&self, state: ConnectionState) {
+        *self.connection_state.borrow_mut() = state.clone();
+        Self::invoke(&self.on_state_change, state);
+    }
+
+    // Verificar que el evento viene del socket actual y no de uno reemplazado
+    fn is_current_socket(&self, ws: &WebSocket) -> bool {
+        self.websocket.borrow().as_ref() == Some(ws)
+    }
+
+    // Cancelar un intento de reconexión pendiente
+    fn cancel_reconnect(&self) {
+        clear_timer(&self.reconnect_timer);
+    }
+
+    // Detener el heartbeat del socket actual
+    fn stop_heartbeat(&self) {
+        clear_timer(&self.heartbeat_timer);
+        clear_timer(&self.pong_timer);
+    }
+
+    // Limpiar lo que depende del socket caído: heartbeat, auth y peticiones en
+    // curso. Las de mensajes sin acuse siguen esperando: se reenvían al reconectar
+    // y solo se rechazan si se renuncia a entregarlos.
+    fn connection_lost(&self) {
+        self.stop_heartbeat();
+        clear_timer(&self.auth_timer);
+        self.reject_unbuffered_requests("Connection lost before a response was received");
+    }
+
+    // Cerrar el socket actual sin disparar la reconexión.
+    // Devuelve `true` si había un socket abierto.
+    fn close_socket(&self) -> Result<bool, JsValue> {
+        self.cancel_reconnect();
+        self.connection_lost();
+        self.fail_all_deliveries("Connection closed before the server acknowledged the message");
+        self.current_reconnect_attempts.set(0);
+        
+        let websocket = self.websocket.borrow_mut().take();
+        if let Some(ws) = websocket {
+            Self::detach_and_close(&ws, NORMAL_CLOSURE_CODE)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    // Quitar los listeners para que el cierre manual no dispare la reconexión
+    fn detach_and_close(ws: &WebSocket, code: u16) -> Result<(), JsValue> {
+        ws.set_onopen(None);
+        ws.set_onmessage(None);
+        ws.set_onerror(None);
+        ws.set_onclose(None);
+        
+        ws.close_with_code(code)
+            .map_err(|e| JsValue::from_str(&format!("Failed to close WebSocket: {:?}", e)))
+    }
+
+    // Resolver la petición pendiente que corresponde a un `ServerResponse`
+    fn resolve_request(&self, message: &WebSocketMessage) {
+        let WebSocketMessage::ServerResponse { message_id, .. } = message else { return };
+        
+        let pending = self.pending_requests.borrow_mut().remove(message_id);
+        if let Some(pending) = pending {
+            cancel_timer(pending.timer);
+            if let Ok(json) = serde_json::to_string(message) {
+                let _ = pending.resolve.call1(&JsValue::NULL, &JsValue::from_str(&json));
+            }
+        }
+    }
+
+    fn reject_request(&self, message_id: &str, reason: &str) {
+        let pending = self.pending_requests.borrow_mut().remove(message_id);
+        if let Some(pending) = pending {
+            cancel_timer(pending.timer);
+            let _ = pending.reject.call1(&JsValue::NULL, &JsValue::from_str(reason));
+        }
+    }
+
+    // Mientras llega la respuesta en streaming la petición sigue viva
+    fn track_stream(&self, message: &WebSocketMessage) {
+        let request_id = match message {
+            WebSocketMessage::MessageStart { message_id, reply_to: Some(reply_to) } => {
+                self.streams.borrow_mut().insert(message_id.clone(), reply_to.clone());
+                Some(reply_to.clone())
+            }
+            WebSocketMessage::MessageDelta { message_id, .. } => self.streams.borrow().get(message_id).cloned(),
+            WebSocketMessage::MessageEnd { message_id } => self.streams.borrow_mut().remove(message_id),
+            _ => None,
+        };
+        
+        if let Some(request_id) = request_id {
+            if let Some(pending) = self.pending_requests.borrow_mut().get_mut(&request_id) {
+                pending.last_activity = js_sys::Date::now();
+            }
+        }
+    }
+
+    fn reject_unbuffered_requests(&self, reason: &str) {
+        self.streams.borrow_mut().clear();
+        let pending: Vec<PendingRequest> = {
+            let unacked = self.unacked_messages.borrow();
+            let mut pending_requests = self.pending_requests.borrow_mut();
+            let rejected: Vec<String> = pending_requests.keys()
+                .filter(|id| !unacked.iter().any(|tracked| tracked.message.message_id() == Some(id.as_str())))
+                .cloned()
+                .collect();
+            rejected.iter().filter_map(|id| pending_requests.remove(id)).collect()
+        };
+        for pending in pending {
+            cancel_timer(pending.timer);
+            let _ = pending.reject.call1(&JsValue::NULL, &JsValue::from_str(reason));
+        }
+    }
+
+    // Empezar a seguir la entrega de un mensaje de chat
+    fn track_delivery(&self, message: &WebSocketMessage, sent: bool) {
+        let WebSocketMessage::ChatMessage { id, .. } = message else { return };
+        
+        self.unacked_messages.borrow_mut().push(TrackedMessage { message: message.clone(), sent });
+        let status = if sent { DeliveryStatus::Sent } else { DeliveryStatus::Pending };
+        self.emit_delivery_status(id.clone(), status);
+    }
+
+    fn mark_sent(&self, message: &WebSocketMessage) {
+        let WebSocketMessage::ChatMessage { id, .. } = message else { return };
+        
+        let found = match self.unacked_messages.borrow_mut().iter_mut()
+            .find(|tracked| tracked.message.message_id() == Some(id.as_str()))
+        {
+            Some(tracked) => {
+                tracked.sent = true;
+                true
+            }
+            None => false,
+        };
+        if found {
+            self.emit_delivery_status(id.clone(), DeliveryStatus::Sent);
+        }
+    }
+
+    // Registrar el acuse (`ServerResponse`) de un mensaje seguido. Una respuesta
+    // que lo cita en `reply_to` también confirma que llegó.
+    fn acknowledge(&self, message: &WebSocketMessage) {
+        let (message_id, delivery) = match message {
+            WebSocketMessage::ServerResponse { message_id, status, data } if status == "error" => {
+                (message_id, DeliveryStatus::Failed(data.clone().unwrap_or_else(|| status.clone())))
+            }
+            WebSocketMessage::ServerResponse { message_id, .. }
+            | WebSocketMessage::ChatMessage { reply_to: Some(message_id), .. }
+            | WebSocketMessage::MessageStart { reply_to: Some(message_id), .. } => {
+                (message_id, DeliveryStatus::Acknowledged)
+            }
+            _ => return,
+        };
+        
+        if self.untrack(message_id) {
+            self.emit_delivery_status(message_id.clone(), delivery);
+        }
+    }
+
+    fn fail_delivery(&self, message: &WebSocketMessage, reason: &str) {
+        let WebSocketMessage::ChatMessage { id, .. } = message else { return };
+        
+        if self.untrack(id) {
+            self.fail_tracked(id, reason);
+        }
+    }
+
+    fn fail_all_deliveries(&self, reason: &str) {
+        let failed: Vec<TrackedMessage> = self.unacked_messages.borrow_mut().drain(..).collect();
+        for tracked in failed {
+            if let Some(id) = tracked.message.message_id() {
+                self.fail_tracked(id, reason);
+            }
+        }
+    }
+
+    // Dar por perdido un mensaje ya fuera del seguimiento: notificarlo y soltar
+    // a quien esperaba su respuesta
+    fn fail_tracked(&self, message_id: &str, reason: &str) {
+        self.emit_delivery_status(message_id.to_string(), DeliveryStatus::Failed(reason.to_string()));
+        self.reject_request(message_id, reason);
+    }
+
+    // Quitar un mensaje del seguimiento. Devuelve `true` si estaba siendo seguido.
+    fn untrack(&self, message_id: &str) -> bool {
+        let mut unacked = self.unacked_messages.borrow_mut();
+        let before = unacked.len();
+        unacked.retain(|tracked| tracked.message.message_id() != Some(message_id));
+        unacked.len() != before
+    }
+
+    // Reenviar, con el mismo id, los mensajes enviados que no recibieron acuse
+    fn resend_unacked(&self, ws: &WebSocket) {
+        // Sin acks el servidor no descarta duplicados: mejor dar el mensaje por fallido
+        // que arriesgar a enviarlo dos veces
+        if self.lacks(Capability::Acks) {
+            let sent: Vec<TrackedMessage> = {
+                let mut unacked = self.unacked_messages.borrow_mut();
+                let (sent, pending) = unacked.drain(..).partition(|tracked| tracked.sent);
+                *unacked = pending;
+                sent
+            };
+            for tracked in sent {
+                if let Some(id) = tracked.message.message_id() {
+                    self.fail_tracked(id, "Connection lost before acknowledgement");
+                }
+            }
+            return;
+        }
+        
+        let to_resend: Vec<WebSocketMessage> = self.unacked_messages.borrow().iter()
+            .filter(|tracked| tracked.sent)
+            .map(|tracked| tracked.message.clone())
+            .collect();
+        
+        for message in to_resend {
+            web_sys::console::log_1(&format!("Resending unacknowledged message: {:?}", message.message_id()).into());
+            if let Err(e) = self.send_frame(ws, &message) {
+                web_sys::console::log_1(&format!("Failed to resend message: {:?}", e).into());
+                break;
+            }
+        }
+    }
+
+    fn auth_frame(&self) -> Option<WebSocketMessage> {
+        Some(match self.credentials.borrow().clone()? {
+            AuthCredentials::ApiKey(api_key) => WebSocketMessage::Auth { api_key: Some(api_key), token: None },
+            AuthCredentials::Token(token) => WebSocketMessage::Auth { api_key: None, token: Some(token) },
+        })
+    }
+
+    // Primer frame tras autenticar: reanudar la sesión si el servidor
+    // ya nos asignó una, o pedir una nueva
+    fn handshake_frame(&self) -> Option<WebSocketMessage> {
+        let user_id = self.user_id.borrow().clone()?;
+        
+        let protocol_version = Some(PROTOCOL_VERSION);
+        let capabilities = SUPPORTED_CAPABILITIES.to_vec();
+        
+        Some(match self.session_id.borrow().clone() {
+            Some(session_id) => WebSocketMessage::ResumeSession {
+                user_id,
+                session_id,
+                last_sequence: self.sequence_buffer.borrow().last_delivered(),
+                protocol_version,
+                capabilities,
+            },
+            None => WebSocketMessage::Connected { user_id, session_id: None, protocol_version, capabilities },
+        })
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.capabilities.borrow().contains(&capability)
+    }
+
+    // El servidor ya negoció y no acordó la capacidad. Antes del handshake no se
+    // sabe: se asume que sí para no perder lo que se pida mientras tanto.
+    fn lacks(&self, capability: Capability) -> bool {
+        self.protocol_version.get().is_some() && !self.supports(capability)
+    }
+
+    // Lo acordado con el socket anterior no vale para el nuevo: se negocia otra vez
+    fn reset_protocol(&self) {
+        self.protocol_version.set(None);
+        self.capabilities.borrow_mut().clear();
+    }
+
+    // Volver a entrar en los rooms tras reconectar; el servidor no los recuerda
+    fn restore_rooms(&self, ws: &WebSocket) {
+        if self.lacks(Capability::Rooms) {
+            if !self.rooms.borrow().is_empty() {
+                web_sys::console::log_1(&"Server does not support rooms, skipping subscriptions".into());
+            }
+            return;
+        }
+        
+        let rooms: Vec<String> = self.rooms.borrow().iter().cloned().collect();
+        for room_id in rooms {
+            let _ = self.send_frame(ws, &WebSocketMessage::JoinRoom { room_id });
+        }
+    }
+
+    // Quedarse con la versión y las capacidades que anunció el servidor. Cualquier
+    // versión sirve: con un servidor v1 solo faltan las capacidades opcionales.
+    // Si el servidor no puede hablar con este SDK, lo dice con `protocol_unsupported`.
+    fn negotiate_protocol(&self, protocol_version: Option<u32>, capabilities: &[Capability]) {
+        let version = protocol_version.unwrap_or(LEGACY_PROTOCOL_VERSION);
+        
+        // Solo se usa lo que ambos lados soportan
+        let agreed: Vec<Capability> = capabilities.iter()
+            .copied()
+            .filter(|capability| SUPPORTED_CAPABILITIES.contains(capability))
+            .collect();
+        
+        web_sys::console::log_1(&format!("Protocol negotiated: v{} {:?}", version.min(PROTOCOL_VERSION), agreed).into());
+        self.protocol_version.set(Some(version.min(PROTOCOL_VERSION)));
+        *self.capabilities.borrow_mut() = agreed;
+    }
+
+    // Cliente y servidor no pueden entenderse: reconectar no sirve de nada
+    fn protocol_unsupported(&self, ws: &WebSocket, reason: String) {
+        web_sys::console::log_1(&reason.clone().into());
+        self.emit_message(WebSocketMessage::Error {
+            code: PROTOCOL_UNSUPPORTED.to_string(),
+            message: reason.clone(),
+        });
+        self.shut_down(ws, &reason, NORMAL_CLOSURE_CODE);
+        self.set_state(ConnectionState::Error(format!("{}: {}", PROTOCOL_UNSUPPORTED, reason)));
+    }
+
+    // Cerrar el socket sin reconectar, fallando todo lo pendiente
+    fn shut_down(&self, ws: &WebSocket, reason: &str, code: u16) {
+        self.emit_error(reason.to_string());
+        self.connection_lost();
+        self.fail_all_deliveries(reason);
+        let _ = Self::detach_and_close(ws, code);
+    }
+
+    fn clear_session(&self) {
+        *self.session_id.borrow_mut() = None;
+        self.reset_sequence();
+    }
+
+    fn reset_sequence(&self) {
+        clear_timer(&self.reorder_timer);
+        self.sequence_buffer.borrow_mut().reset();
+    }
+
+    // Guardar el session_id que asigna el servidor. Si la sesión ya no existe,
+    // olvidarla y pedir una nueva por el mismo socket.
+    fn handle_session_frame(&self, ws: &WebSocket, message: &WebSocketMessage) -> bool {
+        match message {
+            WebSocketMessage::Connected { protocol_version, capabilities, session_id, .. } => {
+                self.negotiate_protocol(*protocol_version, capabilities);
+                
+                let Some(session_id) = session_id else { return false };
+                let previous = self.session_id.replace(Some(session_id.clone()));
+                if previous.as_ref() != Some(session_id) {
+                    // Sesión distinta: el servidor no pudo reanudar la anterior
+                    self.reset_sequence();
+                }
+                web_sys::console::log_1(&format!("Session established: {}", session_id).into());
+            }
+            // El servidor no habla ninguna versión que este SDK entienda
+            WebSocketMessage::Error { code, message } if code == PROTOCOL_UNSUPPORTED => {
+                self.protocol_unsupported(ws, message.clone());
+                return true;
+            }
+            WebSocketMessage::Error { code, .. } if code == "session_expired" || code == "session_not_found" => {
+                web_sys::console::log_1(&"Session could not be resumed, starting a new one".into());
+                self.clear_session();
+                if let Some(handshake) = self.handshake_frame() {
+                    let _ = self.send_frame(ws, &handshake);
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    // Pasar un frame ya ordenado por el resto del pipeline y a los listeners
+    fn dispatch(&self, ws: &WebSocket, message: WebSocketMessage) {
+        if self.handle_control_frame(ws, &message) {
+            return;
+        }
+        // `protocol_unsupported` ya se notificó a los listeners
+        if self.handle_session_frame(ws, &message) {
+            return;
+        }
+        // Sin streaming acordado no se esperan respuestas por partes
+        if self.lacks(Capability::Streaming) && matches!(message,
+            WebSocketMessage::MessageStart { .. } | WebSocketMessage::MessageDelta { .. } | WebSocketMessage::MessageEnd { .. })
+        {
+            web_sys::console::log_1(&"Server did not negotiate streaming, dropping stream frame".into());
+            return;
+        }
+        self.acknowledge(&message);
+        self.track_stream(&message);
+        self.resolve_request(&message);
+        self.emit_message(message);
+    }
+
+    // Responder a los frames de control. Devuelve `true` si el frame se consumió aquí.
+    fn handle_control_frame(&self, ws: &WebSocket, message: &WebSocketMessage) -> bool {
+        match message {
+            WebSocketMessage::Ping { timestamp } => {
+                let _ = self.send_frame(ws, &WebSocketMessage::Pong { timestamp: *timestamp });
+                true
+            }
+            WebSocketMessage::Pong { .. } => {
+                clear_timer(&self.pong_timer);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Serializar con el codec acordado y enviar un frame por el socket
+    fn send_frame(&self, ws: &WebSocket, message: &WebSocketMessage) -> Result<(), JsValue> {
+        let encoded = self.codec.borrow().encode(message).map_err(|e| JsValue::from_str(&e))?;
+        
+        match encoded {
+            EncodedFrame::Text(text) => ws.send_with_str(&text),
+            EncodedFrame::Binary(bytes) => ws.send_with_u8_array(&bytes),
+        }
+        .map_err(|e| JsValue::from_str(&format!("Failed to send message: {:?}", e)))
+    }
+
+    // Quedarse con el codec del subprotocolo que eligió el servidor.
+    // Sin subprotocolos ofrecidos no hay negociación y se habla JSON.
+    fn select_codec(&self, ws: &WebSocket) {
+        let subprotocol = ws.protocol();
+        let codec = self.codecs.borrow().iter()
+            .find(|codec| codec.subprotocol() == subprotocol)
+            .cloned()
+            .unwrap_or_else(|| Rc::new(JsonCodec));
+        
+        web_sys::console::log_1(&format!("Using codec: {}", codec.subprotocol()).into());
+        *self.codec.borrow_mut() = codec;
+    }
+
+    // Guardar un mensaje hasta la próxima conexión, descartando el más antiguo si la cola está llena
+    fn enqueue(&self, message: WebSocketMessage) {
+        self.drop_expired();
+        
+        let dropped = {
+            let mut queue = self.outbound_queue.borrow_mut();
+            let dropped = if queue.len() >= self.queue_max_size.get() {
+                queue.pop_front()
+            } else {
+                None
+            };
+            queue.push_back(QueuedMessage { message, queued_at: js_sys::Date::now() });
+            dropped
+        };
+        
+        if let Some(dropped) = dropped {
+            self.emit_error(format!("Outbound queue full, dropped message: {:?}", dropped.message));
+            self.fail_delivery(&dropped.message, "Outbound queue full");
+        }
+    }
+
+    // Quitar de la cola los mensajes que superaron la edad máxima
+    fn drop_expired(&self) {
+        let max_age = self.queue_max_age_ms.get() as f64;
+        let now = js_sys::Date::now();
+        
+        let expired: VecDeque<QueuedMessage> = {
+            let mut queue = self.outbound_queue.borrow_mut();
+            let (expired, fresh) = queue.drain(..).partition(|queued| now - queued.queued_at > max_age);
+            *queue = fresh;
+            expired
+        };
+        
+        for queued in expired {
+            self.emit_error(format!("Queued message expired before sending: {:?}", queued.message));
+            self.fail_delivery(&queued.message, "Message expired before it could be sent");
+        }
+    }
+
+    // Enviar en orden los mensajes encolados mientras no había conexión
+    fn flush_queue(&self, ws: &WebSocket) {
+        self.drop_expired();
+        
+        loop {
+            let next = self.outbound_queue.borrow_mut().pop_front();
+            let Some(queued) = next else { break };
+            
+            if let Err(e) = self.send_frame(ws, &queued.message) {
+                web_sys::console::log_1(&format!("Failed to flush queued message: {:?}", e).into());
+                self.outbound_queue.borrow_mut().push_front(queued);
+                break;
+            }
+            self.mark_sent(&queued.message);
+        }
+    }
+
+    // Los frames de un room van a sus listeners; si el room no tiene, al general
+    fn emit_message(&self, message: WebSocketMessage) {
+        let room_slot = message.room_id()
+            .and_then(|room_id| self.on_room_message.borrow().get(room_id).cloned());
+        
+        match room_slot {
+            Some(slot) => Self::invoke(&slot, message),
+            None => Self::invoke(&self.on_message, message),
+        }
+    }
+
+    fn emit_error(&self, error: String) {
+        Self::invoke(&self.on_error, error);
+    }
+
+    fn emit_delivery_status(&self, message_id: String, status: DeliveryStatus) {
+        Self::invoke(&self.on_delivery_status, (message_id, status));
+    }
+
+    // Llamar un callback registrado. Se saca del slot mientras se ejecuta para
+    // que pueda, por ejemplo, enviar mensajes o registrar un callback nuevo.
+    fn invoke<T>(slot: &CallbackSlot<T>, value: T) {
+        let callback = slot.borrow_mut().take();
+        if let Some(mut callback) = callback {
+            callback(value);
+            
+            let mut slot = slot.borrow_mut();
+            if slot.is_none() {
+                *slot = Some(callback);
+            }
+        }
+    }
 }
 
 impl WebSocketManager {
+    pub fn downgrade(&self) -> WeakWebSocketManager {
+        WeakWebSocketManager { inner: Rc::downgrade(&self.inner) }
+    }
+
     pub fn new(url: String) -> Self {
         Self {
-            websocket: None,
-            url,
-            connection_state: Rc::new(RefCell::new(ConnectionState::Disconnected)),
-            auto_reconnect: true,
-            reconnect_interval: 3000, // 3 seconds
-            max_reconnect_attempts: 5,
-            current_reconnect_attempts: 0,
-            on_message: None,
-            on_state_change: None,
-            on_error: None,
-            user_id: None,
-            session_id: None,
+            inner: Rc::new(ConnectionInner {
+                url,
+                websocket: RefCell::new(None),
+                connection_state: RefCell::new(ConnectionState::Disconnected),
+                auto_reconnect: Cell::new(true),
+                reconnect_interval: Cell::new(3000), // 3 seconds
+                max_reconnect_attempts: Cell::new(5),
+                current_reconnect_attempts: Cell::new(0),
+                reconnect_timer: Cell::new(None),
+                heartbeat_interval_ms: Cell::new(DEFAULT_HEARTBEAT_INTERVAL_MS),
+                heartbeat_timeout_ms: Cell::new(DEFAULT_HEARTBEAT_TIMEOUT_MS),
+                heartbeat_timer: Cell::new(None),
+                pong_timer: Cell::new(None),
+                pending_requests: RefCell::new(HashMap::new()),
+                streams: RefCell::new(HashMap::new()),
+                unacked_messages: RefCell::new(Vec::new()),
+                outbound_queue: RefCell::new(VecDeque::new()),
+                queue_max_size: Cell::new(DEFAULT_QUEUE_MAX_SIZE),
+                queue_max_age_ms: Cell::new(DEFAULT_QUEUE_MAX_AGE_MS),
+                credentials: RefCell::new(None),
+                auth_timer: Cell::new(None),
+                user_id: RefCell::new(None),
+                session_id: RefCell::new(None),
+                codecs: RefCell::new(Vec::new()),
+                codec: RefCell::new(Rc::new(JsonCodec)),
+                protocol_version: Cell::new(None),
+                capabilities: RefCell::new(Vec::new()),
+                sequence_buffer: RefCell::new(SequenceBuffer::new(DEFAULT_REORDER_WINDOW)),
+                reorder_timer: Cell::new(None),
+                rooms: RefCell::new(BTreeSet::new()),
+                on_message: RefCell::new(None),
+                on_state_change: RefCell::new(None),
+                on_error: RefCell::new(None),
+                on_delivery_status: RefCell::new(None),
+                on_room_message: RefCell::new(HashMap::new()),
+            }),
         }
     }
 
     // Configurar callbacks
-    pub fn on_message<F>(&mut self, callback: F) 
+    pub fn on_message<F>(&self, callback: F) 
     where 
         F: FnMut(WebSocketMessage) + 'static 
     {
-        self.on_message = Some(Box::new(callback));
+        *self.inner.on_message.borrow_mut() = Some(Box::new(callback));
     }
 
-    pub fn on_state_change<F>(&mut self, callback: F) 
+    pub fn on_state_change<F>(&self, callback: F) 
     where 
         F: FnMut(ConnectionState) + 'static 
     {
-        self.on_state_change = Some(Box::new(callback));
+        *self.inner.on_state_change.borrow_mut() = Some(Box::new(callback));
     }
 
-    pub fn on_error<F>(&mut self, callback: F) 
+    pub fn on_error<F>(&self, callback: F) 
     where 
         F: FnMut(String) + 'static 
     {
-        self.on_error = Some(Box::new(callback));
+        *self.inner.on_error.borrow_mut() = Some(Box::new(callback));
+    }
+
+    // Cambios de estado de entrega (pendiente, enviado, confirmado, fallido) por message_id
+    pub fn on_delivery_status<F>(&self, mut callback: F) 
+    where 
+        F: FnMut(String, DeliveryStatus) + 'static 
+    {
+        *self.inner.on_delivery_status.borrow_mut() = Some(Box::new(move |(message_id, status)| {
+            callback(message_id, status)
+        }));
+    }
+
+    // Mensajes de un room concreto. Los frames con room sin listener propio
+    // llegan por `on_message`.
+    pub fn on_room_message<F>(&self, room_id: String, callback: F) 
+    where 
+        F: FnMut(WebSocketMessage) + 'static 
+    {
+        let slot: Rc<CallbackSlot<WebSocketMessage>> = Rc::new(RefCell::new(Some(Box::new(callback))));
+        self.inner.on_room_message.borrow_mut().insert(room_id, slot);
     }
 
     // Conectar al WebSocket
-    pub async fn connect(&mut self, user_id: String) -> Result<(), JsValue> {
-        self.user_id = Some(user_id.clone());
-        self.set_connection_state(ConnectionState::Connecting);
+    pub async fn connect(&self, user_id: String) -> Result<(), JsValue> {
+        // La sesión pertenece al usuario: otro user_id empieza una nueva
+        let previous_user = self.inner.user_id.replace(Some(user_id.clone()));
+        if previous_user.as_ref() != Some(&user_id) {
+            self.inner.clear_session();
+        }
+        
+        // Reemplazar cualquier socket anterior (por ejemplo, tras agotar los reintentos).
+        // Sin socket no hay nada que cerrar: lo encolado antes de conectar se envía al abrir.
+        let has_socket = self.inner.websocket.borrow().is_some();
+        if has_socket {
+            self.inner.close_socket()?;
+        }
+        self.inner.set_state(ConnectionState::Connecting);
         
-        web_sys::console::log_1(&format!("Connecting to WebSocket: {}", self.url).into());
+        Self::open_socket(&self.inner)
+    }
+
+    // Crear el WebSocket y configurar sus event listeners
+    fn open_socket(inner: &Rc<ConnectionInner>) -> Result<(), JsValue> {
+        web_sys::console::log_1(&format!("Connecting to WebSocket: {}", inner.url).into());
         
-        // Crear WebSocket
-        let ws = WebSocket::new(&self.url)
-            .map_err(|e| JsValue::from_str(&format!("Failed to create WebSocket: {:?}", e)))?;
+        // Sin codecs configurados no se ofrece subprotocolo: un servidor que no
+        // negocia rechazaría el handshake si se le ofreciera alguno
+        let subprotocols: js_sys::Array = inner.codecs.borrow().iter()
+            .map(|codec| JsValue::from_str(codec.subprotocol()))
+            .collect();
+        let ws = if subprotocols.length() == 0 {
+            WebSocket::new(&inner.url)
+        } else {
+            WebSocket::new_with_str_sequence(&inner.url, &subprotocols)
+        };
+        let ws = ws.map_err(|e| JsValue::from_str(&format!("Failed to create WebSocket: {:?}", e)))?;
+        // Los frames binarios llegan como ArrayBuffer en lugar de Blob
+        ws.set_binary_type(BinaryType::Arraybuffer);
         
-        // Configurar event listeners
-        self.setup_event_listeners(&ws).await?;
+        Self::setup_event_listeners(inner, &ws);
         
-        self.websocket = Some(ws);
+        *inner.websocket.borrow_mut() = Some(ws);
         Ok(())
     }
 
     // Configurar event listeners
-    async fn setup_event_listeners(&mut self, ws: &WebSocket) -> Result<(), JsValue> {
-        let state_ref = self.connection_state.clone();
-        
+    fn setup_event_listeners(inner: &Rc<ConnectionInner>, ws: &WebSocket) {
         // OnOpen
         {
-            let state_ref = state_ref.clone();
-            let user_id = self.user_id.clone();
+            let inner_ref = Rc::downgrade(inner);
             let ws_clone = ws.clone();
             
             let onopen_callback = Closure::wrap(Box::new(move |_event: web_sys::Event| {
                 web_sys::console::log_1(&"WebSocket connection opened".into());
                 
-                // Cambiar estado a conectado
-                *state_ref.borrow_mut() = ConnectionState::Connected;
+                let Some(inner) = inner_ref.upgrade() else { return };
                 
-                // Enviar mensaje de conexión si tenemos user_id
-                if let Some(ref user_id) = user_id {
-                    let connect_msg = WebSocketMessage::Connected {
-                        user_id: user_id.clone(),
-                        session_id: js_sys::Date::now().to_string(), // Simple session ID
-                    };
-                    
-                    if let Ok(json) = serde_json::to_string(&connect_msg) {
-                        let _ = ws_clone.send_with_str(&json);
-                    }
-                }
+                inner.reset_protocol();
+                inner.select_codec(&ws_clone);
+                
+                // Sin credenciales no hay paso de autenticación
+                let Some(auth) = inner.auth_frame() else {
+                    Self::on_authenticated(&inner, &ws_clone);
+                    return;
+                };
+                
+                inner.set_state(ConnectionState::Authenticating);
+                let _ = inner.send_frame(&ws_clone, &auth);
+                
+                let inner_ref = Rc::downgrade(&inner);
+                let ws_for_timeout = ws_clone.clone();
+                let timeout_callback = Closure::once_into_js(move || {
+                    let Some(inner) = inner_ref.upgrade() else { return };
+                    inner.auth_timer.set(None);
+                    Self::abandon_connection(&inner, &ws_for_timeout, "Authentication timed out", AUTH_TIMEOUT_CODE);
+                });
+                inner.auth_timer.set(set_timer(&timeout_callback, AUTH_TIMEOUT_MS));
             }) as Box<dyn FnMut(_)>);
             
             ws.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
@@ -167,24 +1087,35 @@ impl WebSocketManager {
 
         // OnMessage
         {
+            let inner_ref = Rc::downgrade(inner);
+            let ws_clone = ws.clone();
+            
             let onmessage_callback = Closure::wrap(Box::new(move |event: MessageEvent| {
                 web_sys::console::log_1(&"WebSocket message received".into());
                 
-                if let Ok(text) = event.data().dyn_into::<js_sys::JsString>() {
-                    let message_str = text.as_string().unwrap_or_default();
-                    
-                    // Intentar parsear el mensaje
-                    match serde_json::from_str::<WebSocketMessage>(&message_str) {
-                        Ok(ws_message) => {
-                            web_sys::console::log_1(&format!("Parsed message: {:?}", ws_message).into());
-                            // Aquí llamaríamos al callback on_message si estuviera configurado
-                        }
-                        Err(e) => {
-                            web_sys::console::log_1(&format!("Failed to parse message: {}", e).into());
-                        }
-                    }
+                let Some(inner) = inner_ref.upgrade() else { return };
+                
+                let data = event.data();
+                let encoded = if let Some(text) = data.as_string() {
+                    EncodedFrame::Text(text)
+                } else if let Ok(buffer) = data.dyn_into::<js_sys::ArrayBuffer>() {
+                    EncodedFrame::Binary(js_sys::Uint8Array::new(&buffer).to_vec())
                 } else {
-                    web_sys::console::log_1(&"Received non-text message".into());
+                    web_sys::console::log_1(&"Received unsupported message data".into());
+                    return;
+                };
+                
+                // Intentar parsear el mensaje con el codec acordado
+                let decoded = inner.codec.borrow().decode(&encoded);
+                match decoded {
+                    Ok(frame) => {
+                        web_sys::console::log_1(&format!("Parsed message: {:?}", frame).into());
+                        Self::handle_frame(&inner, &ws_clone, frame);
+                    }
+                    Err(error_msg) => {
+                        web_sys::console::log_1(&error_msg.clone().into());
+                        inner.emit_error(error_msg);
+                    }
                 }
             }) as Box<dyn FnMut(_)>);
             
@@ -194,13 +1125,18 @@ impl WebSocketManager {
 
         // OnError
         {
-            let state_ref = state_ref.clone();
+            let inner_ref = Rc::downgrade(inner);
+            let ws_clone = ws.clone();
             
             let onerror_callback = Closure::wrap(Box::new(move |event: ErrorEvent| {
                 let error_msg = format!("WebSocket error: {:?}", event);
                 web_sys::console::log_1(&error_msg.clone().into());
                 
-                *state_ref.borrow_mut() = ConnectionState::Error(error_msg);
+                let Some(inner) = inner_ref.upgrade() else { return };
+                if inner.is_current_socket(&ws_clone) {
+                    inner.emit_error(error_msg.clone());
+                    inner.set_state(ConnectionState::Error(error_msg));
+                }
             }) as Box<dyn FnMut(_)>);
             
             ws.set_onerror(Some(onerror_callback.as_ref().unchecked_ref()));
@@ -209,60 +1145,352 @@ impl WebSocketManager {
 
         // OnClose
         {
-            let state_ref = state_ref.clone();
-            let auto_reconnect = self.auto_reconnect;
+            let inner_ref = Rc::downgrade(inner);
+            let ws_clone = ws.clone();
             
             let onclose_callback = Closure::wrap(Box::new(move |event: CloseEvent| {
                 let close_msg = format!("WebSocket closed: code={}, reason={}", event.code(), event.reason());
                 web_sys::console::log_1(&close_msg.into());
                 
-                *state_ref.borrow_mut() = ConnectionState::Disconnected;
+                let Some(inner) = inner_ref.upgrade() else { return };
+                if !inner.is_current_socket(&ws_clone) {
+                    return;
+                }
+                inner.connection_lost();
                 
-                // Auto-reconnect logic aquí si es necesario
-                if auto_reconnect && event.code() != 1000 { // 1000 = normal closure
-                    web_sys::console::log_1(&"Will attempt to reconnect...".into());
-                    // Implementar lógica de reconexión
+                if inner.auto_reconnect.get() && event.code() != NORMAL_CLOSURE_CODE {
+                    Self::schedule_reconnect(&inner);
+                } else {
+                    inner.set_state(ConnectionState::Disconnected);
                 }
             }) as Box<dyn FnMut(_)>);
             
             ws.set_onclose(Some(onclose_callback.as_ref().unchecked_ref()));
             onclose_callback.forget();
         }
+    }
 
-        Ok(())
+    // Programar el siguiente intento de reconexión con backoff exponencial
+    fn schedule_reconnect(inner: &Rc<ConnectionInner>) {
+        let attempt = inner.current_reconnect_attempts.get();
+        
+        if attempt >= inner.max_reconnect_attempts.get() {
+            let error_msg = format!("Could not reconnect after {} attempts", attempt);
+            web_sys::console::log_1(&error_msg.clone().into());
+            inner.emit_error(error_msg.clone());
+            inner.fail_all_deliveries(&error_msg);
+            inner.set_state(ConnectionState::Error(error_msg));
+            return;
+        }
+        
+        inner.current_reconnect_attempts.set(attempt + 1);
+        inner.set_state(ConnectionState::Reconnecting);
+        
+        let delay = backoff_delay(inner.reconnect_interval.get(), attempt);
+        web_sys::console::log_1(&format!(
+            "Reconnecting in {}ms (attempt {}/{})",
+            delay, attempt + 1, inner.max_reconnect_attempts.get()
+        ).into());
+        
+        let inner_ref = Rc::downgrade(inner);
+        let reconnect_callback = Closure::once_into_js(move || {
+            let Some(inner) = inner_ref.upgrade() else { return };
+            inner.reconnect_timer.set(None);
+            
+            if let Err(e) = Self::open_socket(&inner) {
+                web_sys::console::log_1(&format!("Reconnect attempt failed: {:?}", e).into());
+                Self::schedule_reconnect(&inner);
+            }
+        });
+        
+        inner.reconnect_timer.set(set_timer(&reconnect_callback, delay));
+    }
+
+    // Conexión lista para el chat: abrir la sesión y enviar lo pendiente
+    fn on_authenticated(inner: &Rc<ConnectionInner>, ws: &WebSocket) {
+        // Conexión exitosa: reiniciar el contador de reconexión
+        inner.current_reconnect_attempts.set(0);
+        
+        // Abrir o reanudar la sesión si tenemos user_id
+        if let Some(handshake) = inner.handshake_frame() {
+            let _ = inner.send_frame(ws, &handshake);
+        }
+        inner.restore_rooms(ws);
+        
+        // Reenviar lo que quedó sin acuse y vaciar la cola antes de notificar,
+        // para que lo pendiente salga primero y en orden
+        inner.resend_unacked(ws);
+        inner.flush_queue(ws);
+        inner.set_state(ConnectionState::Connected);
+        
+        Self::schedule_heartbeat(inner, ws);
+    }
+
+    // Respuesta del servidor al frame `Auth`. Devuelve `true` si se consumió.
+    fn handle_auth_frame(inner: &Rc<ConnectionInner>, ws: &WebSocket, message: &WebSocketMessage) -> bool {
+        if !matches!(*inner.connection_state.borrow(), ConnectionState::Authenticating) {
+            return false;
+        }
+        
+        match message {
+            WebSocketMessage::AuthOk { .. } => {
+                clear_timer(&inner.auth_timer);
+                web_sys::console::log_1(&"WebSocket authenticated".into());
+                Self::on_authenticated(inner, ws);
+                true
+            }
+            WebSocketMessage::Error { code, message } => {
+                clear_timer(&inner.auth_timer);
+                
+                // Reintentar con las mismas credenciales no sirve: no se reconecta
+                let error_msg = format!("Authentication failed ({}): {}", code, message);
+                web_sys::console::log_1(&error_msg.clone().into());
+                inner.shut_down(ws, &error_msg, AUTH_FAILED_CODE);
+                inner.set_state(ConnectionState::AuthFailed(error_msg));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Ordenar el frame por su secuencia antes de entregarlo
+    fn handle_frame(inner: &Rc<ConnectionInner>, ws: &WebSocket, frame: SequencedFrame) {
+        if Self::handle_auth_frame(inner, ws, &frame.message) {
+            return;
+        }
+        
+        let Some(seq) = frame.seq else {
+            inner.dispatch(ws, frame.message);
+            return;
+        };
+        
+        let outcome = inner.sequence_buffer.borrow_mut().push(seq, frame.message);
+        Self::apply_sequence_outcome(inner, ws, outcome);
+    }
+
+    fn apply_sequence_outcome(inner: &Rc<ConnectionInner>, ws: &WebSocket, outcome: SequenceOutcome) {
+        if outcome.duplicate {
+            web_sys::console::log_1(&"Dropped duplicate frame".into());
+        }
+        
+        for message in outcome.ready {
+            inner.dispatch(ws, message);
+        }
+        
+        // Hueco en la secuencia: pedir al servidor los frames perdidos
+        if let Some((from_sequence, to_sequence)) = outcome.gap {
+            web_sys::console::log_1(&format!("Missing frames {}..={}, requesting history", from_sequence, to_sequence).into());
+            let history_request = WebSocketMessage::HistoryRequest {
+                session_id: inner.session_id.borrow().clone(),
+                from_sequence,
+                to_sequence,
+            };
+            let _ = inner.send_frame(ws, &history_request);
+        }
+        
+        // Si quedan frames retenidos, no esperar al faltante indefinidamente
+        if !inner.sequence_buffer.borrow().has_pending() {
+            clear_timer(&inner.reorder_timer);
+        } else if inner.reorder_timer.get().is_none() {
+            let inner_ref = Rc::downgrade(inner);
+            let reorder_callback = Closure::once_into_js(move || {
+                let Some(inner) = inner_ref.upgrade() else { return };
+                inner.reorder_timer.set(None);
+                
+                // Puede haber reconectado mientras tanto: usar el socket actual
+                let websocket = inner.websocket.borrow().clone();
+                let Some(ws) = websocket else { return };
+                
+                let outcome = inner.sequence_buffer.borrow_mut().flush();
+                Self::apply_sequence_outcome(&inner, &ws, outcome);
+            });
+            inner.reorder_timer.set(set_timer(&reorder_callback, REORDER_TIMEOUT_MS));
+        }
     }
 
-    // Enviar mensaje
+    // Programar el siguiente ping del heartbeat para este socket
+    fn schedule_heartbeat(inner: &Rc<ConnectionInner>, ws: &WebSocket) {
+        let interval = inner.heartbeat_interval_ms.get();
+        if interval == 0 {
+            return;
+        }
+        
+        let inner_ref = Rc::downgrade(inner);
+        let ws_clone = ws.clone();
+        let heartbeat_callback = Closure::once_into_js(move || {
+            let Some(inner) = inner_ref.upgrade() else { return };
+            inner.heartbeat_timer.set(None);
+            
+            if !inner.is_current_socket(&ws_clone) || ws_clone.ready_state() != WebSocket::OPEN {
+                return;
+            }
+            
+            // Solo un ping pendiente a la vez
+            if inner.pong_timer.get().is_none() {
+                let ping = WebSocketMessage::Ping { timestamp: js_sys::Date::now() as u64 };
+                let _ = inner.send_frame(&ws_clone, &ping);
+                
+                let inner_ref = Rc::downgrade(&inner);
+                let ws_for_timeout = ws_clone.clone();
+                let timeout_callback = Closure::once_into_js(move || {
+                    let Some(inner) = inner_ref.upgrade() else { return };
+                    inner.pong_timer.set(None);
+                    Self::abandon_connection(&inner, &ws_for_timeout, "Heartbeat timeout: no pong received", HEARTBEAT_TIMEOUT_CODE);
+                });
+                inner.pong_timer.set(set_timer(&timeout_callback, inner.heartbeat_timeout_ms.get()));
+            }
+            
+            Self::schedule_heartbeat(&inner, &ws_clone);
+        });
+        
+        inner.heartbeat_timer.set(set_timer(&heartbeat_callback, interval));
+    }
+
+    // El servidor no respondió (al ping o a la autenticación): dar el socket por
+    // muerto y reconectar. Los sockets semiabiertos detrás de algunos proxies
+    // nunca disparan onclose.
+    fn abandon_connection(inner: &Rc<ConnectionInner>, ws: &WebSocket, reason: &str, code: u16) {
+        if !inner.is_current_socket(ws) {
+            return;
+        }
+        
+        web_sys::console::log_1(&reason.into());
+        inner.emit_error(reason.to_string());
+        
+        inner.connection_lost();
+        // El socket se queda en el slot (cerrándose) para que los envíos se encolen
+        let _ = ConnectionInner::detach_and_close(ws, code);
+        
+        if inner.auto_reconnect.get() {
+            Self::schedule_reconnect(inner);
+        } else {
+            inner.set_state(ConnectionState::Disconnected);
+        }
+    }
+
+    // Enviar mensaje. Si el socket no está listo (sin conectar todavía, conectando,
+    // autenticando, cerrado o reconectando) el mensaje se encola y se envía al abrir.
     pub fn send_message(&self, message: WebSocketMessage) -> Result<(), JsValue> {
-        if let Some(ref ws) = self.websocket {
-            match ws.ready_state() {
-                WebSocket::OPEN => {
-                    let json = serde_json::to_string(&message)
-                        .map_err(|e| JsValue::from_str(&format!("Failed to serialize message: {}", e)))?;
-                    
-                    ws.send_with_str(&json)
-                        .map_err(|e| JsValue::from_str(&format!("Failed to send message: {:?}", e)))?;
-                    
-                    web_sys::console::log_1(&"Message sent successfully".into());
-                    Ok(())
-                }
-                _ => {
-                    Err(JsValue::from_str("WebSocket is not connected"))
+        let websocket = self.inner.websocket.borrow().clone();
+        match websocket {
+            Some(ws) if ws.ready_state() == WebSocket::OPEN && self.is_connected() => {
+                self.inner.send_frame(&ws, &message)?;
+                self.inner.track_delivery(&message, true);
+                
+                web_sys::console::log_1(&"Message sent successfully".into());
+                Ok(())
+            }
+            _ => {
+                web_sys::console::log_1(&"WebSocket not open, message queued".into());
+                self.inner.track_delivery(&message, false);
+                self.inner.enqueue(message);
+                Ok(())
+            }
+        }
+    }
+
+    // Enviar un mensaje y esperar el `ServerResponse` con su mismo id, tenga datos
+    // o sea solo el acuse. Se rechaza si vence el timeout o si se renuncia a entregar
+    // el mensaje; una caída antes del acuse no la rechaza porque el mensaje se
+    // reenvía al reconectar. Para esperar la respuesta del bot está `ReplyTracker`.
+    pub async fn request(&self, message: WebSocketMessage, timeout_ms: u32) -> Result<WebSocketMessage, JsValue> {
+        let message_id = message.message_id()
+            .ok_or_else(|| JsValue::from_str("Message has no id to correlate a response"))?
+            .to_string();
+        
+        let mut callbacks = None;
+        let promise = js_sys::Promise::new(&mut |resolve, reject| {
+            callbacks = Some((resolve, reject));
+        });
+        let (resolve, reject) = callbacks.ok_or_else(|| JsValue::from_str("Failed to create promise"))?;
+        
+        let timer = Self::schedule_request_timeout(&self.inner, message_id.clone(), timeout_ms);
+        let last_activity = js_sys::Date::now();
+        
+        self.inner.pending_requests.borrow_mut()
+            .insert(message_id.clone(), PendingRequest { resolve, reject, timer, timeout_ms, last_activity });
+        
+        if let Err(e) = self.send_message(message) {
+            if let Some(pending) = self.inner.pending_requests.borrow_mut().remove(&message_id) {
+                cancel_timer(pending.timer);
+            }
+            return Err(e);
+        }
+        
+        let response = JsFuture::from(promise).await?;
+        serde_json::from_str(&response.as_string().unwrap_or_default())
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse response: {}", e)))
+    }
+
+    // Rechazar la petición si no hubo actividad en `timeout_ms`; si la hubo
+    // (por ejemplo, partes de una respuesta en streaming) esperar lo que falta
+    fn schedule_request_timeout(inner: &Rc<ConnectionInner>, message_id: String, delay_ms: u32) -> Option<i32> {
+        let inner_ref = Rc::downgrade(inner);
+        let timeout_callback = Closure::once_into_js(move || {
+            let Some(inner) = inner_ref.upgrade() else { return };
+            
+            let remaining = match inner.pending_requests.borrow().get(&message_id) {
+                Some(pending) => pending.last_activity + pending.timeout_ms as f64 - js_sys::Date::now(),
+                None => return,
+            };
+            
+            if remaining > 0.0 {
+                let timer = Self::schedule_request_timeout(&inner, message_id.clone(), remaining.ceil() as u32);
+                if let Some(pending) = inner.pending_requests.borrow_mut().get_mut(&message_id) {
+                    pending.timer = timer;
                 }
+            } else {
+                inner.reject_request(&message_id, "Request timed out");
             }
+        });
+        set_timer(&timeout_callback, delay_ms)
+    }
+
+    // Crear un mensaje de chat del usuario actual
+    pub fn chat_message(&self, content: String) -> Result<WebSocketMessage, JsValue> {
+        let user_id = self.inner.user_id.borrow().clone()
+            .ok_or_else(|| JsValue::from_str("User ID not set"))?;
+        self.chat_message_with_id(WebSocketMessage::new_id(&user_id), content)
+    }
+
+    // Crear un mensaje de chat con un id ya asignado (por ejemplo, por la UI)
+    pub fn chat_message_with_id(&self, id: String, content: String) -> Result<WebSocketMessage, JsValue> {
+        self.room_chat_message(id, content, None)
+    }
+
+    // Crear un mensaje de chat dentro de un room
+    pub fn room_chat_message(&self, id: String, content: String, room_id: Option<String>) -> Result<WebSocketMessage, JsValue> {
+        if let Some(ref user_id) = *self.inner.user_id.borrow() {
+            Ok(WebSocketMessage::ChatMessage {
+                id,
+                content,
+                user_id: user_id.clone(),
+                timestamp: js_sys::Date::now() as u64,
+                room_id,
+                reply_to: None,
+            })
         } else {
-            Err(JsValue::from_str("WebSocket not initialized"))
+            Err(JsValue::from_str("User ID not set"))
         }
     }
 
     // Enviar mensaje de chat
     pub fn send_chat_message(&self, content: String) -> Result<(), JsValue> {
-        if let Some(ref user_id) = self.user_id {
-            let message = WebSocketMessage::ChatMessage {
-                id: format!("{}-{}", user_id, js_sys::Date::now()),
-                content,
+        let message = self.chat_message(content)?;
+        self.send_message(message)
+    }
+
+    // Indicar que el usuario está escribiendo.
+    // No se encola: un indicador viejo no tiene sentido tras reconectar.
+    pub fn send_typing_indicator(&self, is_typing: bool) -> Result<(), JsValue> {
+        if !self.is_connected() {
+            return Err(JsValue::from_str("WebSocket is not connected"));
+        }
+        
+        if let Some(ref user_id) = *self.inner.user_id.borrow() {
+            let message = WebSocketMessage::UserTyping {
                 user_id: user_id.clone(),
-                timestamp: js_sys::Date::now() as u64,
+                is_typing,
             };
             
             self.send_message(message)
@@ -271,12 +1499,17 @@ impl WebSocketManager {
         }
     }
 
-    // Indicar que el usuario está escribiendo
-    pub fn send_typing_indicator(&self, is_typing: bool) -> Result<(), JsValue> {
-        if let Some(ref user_id) = self.user_id {
-            let message = WebSocketMessage::UserTyping {
+    // Publicar el estado de presencia del usuario ("online", "away", "offline").
+    // Tampoco se encola: al reconectar se publica el estado del momento.
+    pub fn send_presence(&self, status: &str) -> Result<(), JsValue> {
+        if !self.is_connected() {
+            return Err(JsValue::from_str("WebSocket is not connected"));
+        }
+        
+        if let Some(ref user_id) = *self.inner.user_id.borrow() {
+            let message = WebSocketMessage::UserPresence {
                 user_id: user_id.clone(),
-                is_typing,
+                status: status.to_string(),
             };
             
             self.send_message(message)
@@ -285,47 +1518,162 @@ impl WebSocketManager {
         }
     }
 
+    // Suscribirse a un room. Sin conexión, el room se une al conectar.
+    pub fn join_room(&self, room_id: String) -> Result<(), JsValue> {
+        if self.inner.lacks(Capability::Rooms) {
+            return Err(JsValue::from_str("Server does not support rooms"));
+        }
+        if !self.inner.rooms.borrow_mut().insert(room_id.clone()) {
+            return Ok(());
+        }
+        
+        if self.is_connected() {
+            self.send_message(WebSocketMessage::JoinRoom { room_id })?;
+        }
+        Ok(())
+    }
+
+    // Salir de un room y olvidar sus listeners
+    pub fn leave_room(&self, room_id: &str) -> Result<(), JsValue> {
+        self.inner.on_room_message.borrow_mut().remove(room_id);
+        if !self.inner.rooms.borrow_mut().remove(room_id) {
+            return Ok(());
+        }
+        
+        if self.is_connected() && !self.inner.lacks(Capability::Rooms) {
+            self.send_message(WebSocketMessage::LeaveRoom { room_id: room_id.to_string() })?;
+        }
+        Ok(())
+    }
+
+    // Rooms suscritos actualmente
+    pub fn rooms(&self) -> Vec<String> {
+        self.inner.rooms.borrow().iter().cloned().collect()
+    }
+
     // Desconectar
-    pub fn disconnect(&mut self) -> Result<(), JsValue> {
-        if let Some(ref ws) = self.websocket {
-            ws.close().map_err(|e| JsValue::from_str(&format!("Failed to close WebSocket: {:?}", e)))?;
-            self.websocket = None;
-            self.set_connection_state(ConnectionState::Disconnected);
+    pub fn disconnect(&self) -> Result<(), JsValue> {
+        // Un cierre manual termina la sesión; la próxima conexión pide una nueva
+        self.inner.clear_session();
+        
+        if self.inner.close_socket()? {
+            self.inner.set_state(ConnectionState::Disconnected);
         }
         Ok(())
     }
 
     // Obtener estado de conexión
     pub fn get_connection_state(&self) -> ConnectionState {
-        self.connection_state.borrow().clone()
+        self.inner.connection_state.borrow().clone()
     }
 
-    // Cambiar estado de conexión
-    fn set_connection_state(&mut self, state: ConnectionState) {
-        *self.connection_state.borrow_mut() = state.clone();
-        
-        // Llamar callback si existe
-        if let Some(ref mut callback) = self.on_state_change {
-            callback(state);
-        }
+    // Obtener el ID de sesión actual
+    pub fn get_session_id(&self) -> Option<String> {
+        self.inner.session_id.borrow().clone()
+    }
+
+    // Versión del protocolo acordada con el servidor, si ya hubo handshake
+    pub fn protocol_version(&self) -> Option<u32> {
+        self.inner.protocol_version.get()
+    }
+
+    // Si el servidor acordó una capacidad opcional del protocolo
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.inner.supports(capability)
     }
 
     // Verificar si está conectado
     pub fn is_connected(&self) -> bool {
-        matches!(*self.connection_state.borrow(), ConnectionState::Connected)
+        matches!(*self.inner.connection_state.borrow(), ConnectionState::Connected)
+    }
+
+    // Configurar las credenciales del handshake; se usan desde la próxima conexión.
+    // Un token renovado puede fijarse aquí antes de que expire.
+    pub fn set_credentials(&self, credentials: Option<AuthCredentials>) {
+        *self.inner.credentials.borrow_mut() = credentials;
     }
 
     // Configurar opciones de reconexión
-    pub fn set_reconnect_options(&mut self, auto_reconnect: bool, interval_ms: u32, max_attempts: u32) {
-        self.auto_reconnect = auto_reconnect;
-        self.reconnect_interval = interval_ms;
-        self.max_reconnect_attempts = max_attempts;
+    pub fn set_reconnect_options(&self, auto_reconnect: bool, interval_ms: u32, max_attempts: u32) {
+        self.inner.auto_reconnect.set(auto_reconnect);
+        self.inner.reconnect_interval.set(interval_ms);
+        self.inner.max_reconnect_attempts.set(max_attempts);
+    }
+
+    // Configurar los codecs que se ofrecen al servidor, por orden de preferencia.
+    // Aplica desde la próxima conexión. Sin codecs (por defecto) no se negocia y
+    // se habla JSON; si se ofrecen, el servidor tiene que aceptar uno.
+    pub fn set_codecs(&self, codecs: Vec<Rc<dyn FrameCodec>>) {
+        *self.inner.codecs.borrow_mut() = codecs;
+    }
+
+    // Ofrecer MessagePack (con JSON como alternativa) o volver a JSON sin negociar
+    pub fn set_message_pack(&self, enabled: bool) {
+        let codecs: Vec<Rc<dyn FrameCodec>> = if enabled {
+            vec![Rc::new(MessagePackCodec), Rc::new(JsonCodec)]
+        } else {
+            Vec::new()
+        };
+        self.set_codecs(codecs);
+    }
+
+    // Configurar límites de la cola de salida (tamaño y edad máxima)
+    pub fn set_queue_options(&self, max_size: usize, max_age_ms: u32) {
+        self.inner.queue_max_size.set(max_size);
+        self.inner.queue_max_age_ms.set(max_age_ms);
+    }
+
+    // Configurar el heartbeat (intervalo entre pings y espera máxima del pong).
+    // Un intervalo de 0 lo desactiva; aplica desde la próxima conexión.
+    pub fn set_heartbeat_options(&self, interval_ms: u32, timeout_ms: u32) {
+        self.inner.heartbeat_interval_ms.set(interval_ms);
+        self.inner.heartbeat_timeout_ms.set(timeout_ms);
+    }
+
+    // Número de mensajes esperando a ser enviados
+    pub fn queued_messages(&self) -> usize {
+        self.inner.outbound_queue.borrow().len()
     }
 }
 
-// Drop trait para limpiar recursos
-impl Drop for WebSocketManager {
+impl fmt::Debug for WebSocketManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocketManager")
+            .field("url", &self.inner.url)
+            .field("connection_state", &*self.inner.connection_state.borrow())
+            .finish()
+    }
+}
+
+// Drop trait para limpiar recursos cuando se suelta el último handle
+impl Drop for ConnectionInner {
     fn drop(&mut self) {
-        let _ = self.disconnect();
+        let _ = self.close_socket();
+    }
+}
+
+// Programar un callback con setTimeout y devolver su handle
+pub(crate) fn set_timer(callback: &JsValue, delay_ms: u32) -> Option<i32> {
+    let window = web_sys::window()?;
+    match window.set_timeout_with_callback_and_timeout_and_arguments_0(
+        callback.unchecked_ref(),
+        delay_ms as i32,
+    ) {
+        Ok(handle) => Some(handle),
+        Err(e) => {
+            web_sys::console::log_1(&format!("Failed to schedule timer: {:?}", e).into());
+            None
+        }
     }
-}
\ No newline at end of file
+}
+
+// Cancelar un timer guardado en una celda
+pub(crate) fn clear_timer(timer: &Cell<Option<i32>>) {
+    cancel_timer(timer.take());
+}
+
+pub(crate) fn cancel_timer(handle: Option<i32>) {
+    if let (Some(handle), Some(window)) = (handle, web_sys::window()) {
+        window.clear_timeout_with_handle(handle);
+    }
+}
@@ -0,0 +1,178 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use super::websocket_manager::{SequencedFrame, WebSocketMessage};
+
+// Versión del formato de los frames: `{ "v": 2, "type": "chat_message", ... }`.
+// La versión 1 era la representación por defecto de serde, `{ "ChatMessage": { ... } }`.
+pub const WIRE_FORMAT_VERSION: u32 = 2;
+
+// Campos del sobre que no pertenecen al mensaje
+const ENVELOPE_FIELDS: [&str; 2] = ["v", "seq"];
+
+// Frame saliente con la versión del formato
+#[derive(Serialize)]
+struct OutboundFrame<'a> {
+    v: u32,
+    #[serde(flatten)]
+    message: &'a WebSocketMessage,
+}
+
+pub fn encode(message: &WebSocketMessage) -> Result<String, String> {
+    serde_json::to_string(&OutboundFrame { v: WIRE_FORMAT_VERSION, message })
+        .map_err(|e| format!("Failed to serialize message: {}", e))
+}
+
+// Mismo sobre en MessagePack, con los campos por nombre (el tag `type` lo necesita)
+pub fn encode_binary(message: &WebSocketMessage) -> Result<Vec<u8>, String> {
+    rmp_serde::to_vec_named(&OutboundFrame { v: WIRE_FORMAT_VERSION, message })
+        .map_err(|e| format!("Failed to serialize message: {}", e))
+}
+
+// Los frames binarios nacieron con la versión 2: no hay formato anterior que aceptar
+pub fn decode_binary(bytes: &[u8]) -> Result<SequencedFrame, String> {
+    rmp_serde::from_slice(bytes).map_err(|e| format!("Failed to parse binary message: {}", e))
+}
+
+// Acepta el formato actual y, durante la migración, el de la versión 1
+pub fn decode(text: &str) -> Result<SequencedFrame, String> {
+    let value: Value = serde_json::from_str(text)
+        .map_err(|e| format!("Failed to parse message: {}", e))?;
+
+    let value = match value {
+        Value::Object(object) if !object.contains_key("type") => upgrade_legacy(object)?,
+        other => other,
+    };
+
+    serde_json::from_value(value).map_err(|e| format!("Failed to parse message: {}", e))
+}
+
+// `{ "seq": 3, "ChatMessage": { ... } }` -> `{ "seq": 3, "type": "chat_message", ... }`
+fn upgrade_legacy(mut object: Map<String, Value>) -> Result<Value, String> {
+    let variant = object
+        .keys()
+        .find(|key| !ENVELOPE_FIELDS.contains(&key.as_str()))
+        .cloned()
+        .ok_or_else(|| "Failed to parse message: missing `type`".to_string())?;
+
+    let mut upgraded = match object.remove(&variant) {
+        Some(Value::Object(fields)) => fields,
+        _ => return Err(format!("Failed to parse legacy message `{}`", variant)),
+    };
+
+    for (key, value) in object {
+        upgraded.insert(key, value);
+    }
+    upgraded.insert("type".to_string(), Value::String(to_snake_case(&variant)));
+
+    Ok(Value::Object(upgraded))
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn chat_message() -> WebSocketMessage {
+        WebSocketMessage::ChatMessage {
+            id: "user-1-1".to_string(),
+            content: "Hola".to_string(),
+            user_id: "user-1".to_string(),
+            timestamp: 1,
+            room_id: None,
+            reply_to: None,
+        }
+    }
+
+    #[test]
+    fn encodes_the_v2_envelope() {
+        let encoded: Value = serde_json::from_str(&encode(&chat_message()).unwrap()).unwrap();
+
+        assert_eq!(encoded["v"], json!(WIRE_FORMAT_VERSION));
+        assert_eq!(encoded["type"], json!("chat_message"));
+        assert_eq!(encoded["id"], json!("user-1-1"));
+        assert!(encoded.get("room_id").is_none());
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let frame = decode(&encode(&chat_message()).unwrap()).unwrap();
+
+        assert_eq!(frame.seq, None);
+        assert!(matches!(frame.message, WebSocketMessage::ChatMessage { ref content, .. } if content == "Hola"));
+    }
+
+    #[test]
+    fn decodes_the_sequence_number() {
+        let frame = decode(r#"{ "v": 2, "seq": 7, "type": "pong", "timestamp": 5 }"#).unwrap();
+
+        assert_eq!(frame.seq, Some(7));
+        assert!(matches!(frame.message, WebSocketMessage::Pong { timestamp: 5 }));
+    }
+
+    #[test]
+    fn upgrades_legacy_frames() {
+        let legacy = r#"{ "ChatMessage": { "id": "m1", "content": "Hola", "user_id": "bot", "timestamp": 3 } }"#;
+        let frame = decode(legacy).unwrap();
+
+        assert_eq!(frame.seq, None);
+        match frame.message {
+            WebSocketMessage::ChatMessage { id, content, user_id, timestamp, room_id, .. } => {
+                assert_eq!(id, "m1");
+                assert_eq!(content, "Hola");
+                assert_eq!(user_id, "bot");
+                assert_eq!(timestamp, 3);
+                assert_eq!(room_id, None);
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn upgrades_legacy_frames_keeping_the_envelope() {
+        let legacy = r#"{ "seq": 4, "ServerResponse": { "message_id": "m1", "status": "ok", "data": "Listo" } }"#;
+        let frame = decode(legacy).unwrap();
+
+        assert_eq!(frame.seq, Some(4));
+        assert!(matches!(
+            frame.message,
+            WebSocketMessage::ServerResponse { ref message_id, ref data, .. }
+                if message_id == "m1" && data.as_deref() == Some("Listo")
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_legacy_frames() {
+        assert!(decode(r#"{ "seq": 1 }"#).is_err());
+        assert!(decode(r#"{ "ChatMessage": "Hola" }"#).is_err());
+        assert!(decode("not json").is_err());
+    }
+
+    #[test]
+    fn round_trips_binary_frames() {
+        let frame = decode_binary(&encode_binary(&chat_message()).unwrap()).unwrap();
+
+        assert!(matches!(frame.message, WebSocketMessage::ChatMessage { ref id, .. } if id == "user-1-1"));
+    }
+
+    #[test]
+    fn converts_variant_names_to_snake_case() {
+        assert_eq!(to_snake_case("ChatMessage"), "chat_message");
+        assert_eq!(to_snake_case("AuthOk"), "auth_ok");
+        assert_eq!(to_snake_case("Ping"), "ping");
+    }
+}